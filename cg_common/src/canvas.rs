@@ -1,17 +1,203 @@
+use std::io;
+use std::path::Path;
+
+use crate::image;
 use crate::math::Point2D;
 
 pub struct Canvas<'a> {
     pub buffer: &'a mut [u32], 
+    pub depth: Option<&'a mut [f64]>, // 1/z per pixel, 0.0 is infinitely far away
     pub width: u32,
     pub height: u32,
 }
 
+// per-draw-call depth buffer behaviour
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DepthMode {
+    pub test: bool,
+    pub write: bool,
+}
+
+impl DepthMode {
+    pub const NONE: Self = Self { test: false, write: false };
+    pub const TEST_ONLY: Self = Self { test: true, write: false }; // transparent passes
+    pub const TEST_AND_WRITE: Self = Self { test: true, write: true };
+}
+
+impl Default for DepthMode {
+    fn default() -> Self {
+        Self::TEST_AND_WRITE
+    }
+}
+
+// returned by the checked pixel accessors, in screen coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfBounds {
+    pub x: i64,
+    pub y: i64,
+}
+
+impl std::fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "pixel ({}, {}) is outside the canvas", self.x, self.y)
+    }
+}
+
+impl std::error::Error for OutOfBounds {}
+
+// Point2D arguments are centered coordinates like the book uses: origin in the
+// middle, +y up. the *_screen variants take plain row/column coordinates with
+// the origin at the top left. anything off the canvas is quietly dropped
 impl<'a> Canvas<'a> {
+    fn to_screen(&self, p: Point2D) -> (i64, i64) {
+        let x = (self.width / 2) as f64 + p.x;
+        let y = (self.height / 2) as f64 - p.y - 1.0;
+        (x.floor() as i64, y.floor() as i64)
+    }
+
+    fn screen_index(&self, x: i64, y: i64) -> Result<usize, OutOfBounds> {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return Err(OutOfBounds { x, y });
+        }
+        Ok(y as usize * self.width as usize + x as usize)
+    }
+
+    fn index(&self, p: Point2D) -> Result<usize, OutOfBounds> {
+        let (x, y) = self.to_screen(p);
+        self.screen_index(x, y)
+    }
+
+    // inclusive (min_x, max_x, min_y, max_y) in centered coordinates
+    pub fn centered_bounds(&self) -> (i32, i32, i32, i32) {
+        let min_x = -((self.width / 2) as i32);
+        let max_y = (self.height / 2) as i32 - 1;
+        (min_x, min_x + self.width as i32 - 1, max_y - self.height as i32 + 1, max_y)
+    }
+
     pub fn put_pixel(&mut self, p: Point2D, color: u32) {
-        let x_norm = (self.width / 2) as f64 + p.x;
-        let y_norm = (self.height / 2) as f64 - p.y - 1.0;
-        let index = ((self.width as f64 * y_norm) + x_norm) as usize;
+        let _ = self.try_put_pixel(p, color);
+    }
+
+    pub fn try_put_pixel(&mut self, p: Point2D, color: u32) -> Result<(), OutOfBounds> {
+        let index = self.index(p)?;
+        self.buffer[index] = color;
+        Ok(())
+    }
+
+    pub fn get_pixel(&self, p: Point2D) -> Option<u32> {
+        self.index(p).ok().map(|i| self.buffer[i])
+    }
+
+    pub fn put_pixel_screen(&mut self, x: i64, y: i64, color: u32) {
+        let _ = self.try_put_pixel_screen(x, y, color);
+    }
 
+    pub fn try_put_pixel_screen(&mut self, x: i64, y: i64, color: u32) -> Result<(), OutOfBounds> {
+        let index = self.screen_index(x, y)?;
         self.buffer[index] = color;
+        Ok(())
+    }
+
+    pub fn get_pixel_screen(&self, x: i64, y: i64) -> Option<u32> {
+        self.screen_index(x, y).ok().map(|i| self.buffer[i])
+    }
+
+    // writes the pixel if it passes the depth test. without a depth buffer
+    // attached this is just put_pixel
+    pub fn put_pixel_depth(&mut self, p: Point2D, inv_z: f64, color: u32, mode: DepthMode) {
+        let Ok(index) = self.index(p) else {
+            return;
+        };
+
+        if let Some(depth) = self.depth.as_deref_mut() {
+            if mode.test && inv_z <= depth[index] {
+                return;
+            }
+            if mode.write {
+                depth[index] = inv_z;
+            }
+        }
+
+        self.buffer[index] = color;
+    }
+
+    // format comes from the extension: .png, .bmp or .ppm
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        image::save(path, self.buffer, self.width, self.height)
+    }
+
+    pub fn clear_depth(&mut self) {
+        if let Some(depth) = self.depth.as_deref_mut() {
+            depth.fill(0.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn off_canvas_writes_are_dropped() {
+        let mut buffer = vec![0; 4 * 4];
+        let mut canvas = Canvas { buffer: &mut buffer, depth: None, width: 4, height: 4 };
+
+        canvas.put_pixel(Point2D { x: -3.0, y: 0.0 }, 0xFFFFFF); // used to wrap a row
+        canvas.put_pixel(Point2D { x: 0.0, y: -3.0 }, 0xFFFFFF); // used to panic
+        assert!(canvas.try_put_pixel(Point2D { x: 2.0, y: 0.0 }, 0xFFFFFF).is_err());
+        assert!(canvas.buffer.iter().all(|c| *c == 0));
+    }
+
+    #[test]
+    fn centered_and_screen_agree() {
+        let mut buffer = vec![0; 4 * 4];
+        let mut canvas = Canvas { buffer: &mut buffer, depth: None, width: 4, height: 4 };
+
+        canvas.put_pixel(Point2D { x: -2.0, y: 1.0 }, 0x123456);
+        assert_eq!(canvas.get_pixel_screen(0, 0), Some(0x123456));
+        assert_eq!(canvas.centered_bounds(), (-2, 1, -2, 1));
+        assert_eq!(canvas.get_pixel(Point2D { x: 1.0, y: -2.0 }), canvas.get_pixel_screen(3, 3));
+    }
+
+    #[test]
+    fn nearer_wins_the_depth_test() {
+        let mut buffer = vec![0; 2 * 2];
+        let mut depth = vec![0.0; 2 * 2];
+        let mut canvas = Canvas { buffer: &mut buffer, depth: Some(&mut depth), width: 2, height: 2 };
+        let p = Point2D { x: -1.0, y: 0.0 }; // top left, index 0
+
+        canvas.put_pixel_depth(p, 0.5, 0x111111, DepthMode::TEST_AND_WRITE);
+        canvas.put_pixel_depth(p, 0.25, 0x222222, DepthMode::TEST_AND_WRITE); // farther, rejected
+        assert_eq!(canvas.get_pixel(p), Some(0x111111));
+
+        canvas.put_pixel_depth(p, 1.0, 0x333333, DepthMode::TEST_AND_WRITE); // nearer
+        assert_eq!(canvas.get_pixel(p), Some(0x333333));
+
+        canvas.clear_depth();
+        canvas.put_pixel_depth(p, 0.1, 0x444444, DepthMode::TEST_AND_WRITE);
+        assert_eq!(canvas.get_pixel(p), Some(0x444444));
+    }
+
+    #[test]
+    fn depth_modes() {
+        let mut buffer = vec![0; 2 * 2];
+        let mut depth = vec![0.0; 2 * 2];
+        let mut canvas = Canvas { buffer: &mut buffer, depth: Some(&mut depth), width: 2, height: 2 };
+        let p = Point2D { x: -1.0, y: 0.0 }; // top left, index 0
+
+        // test only draws but leaves the depth alone, so something farther still gets in after
+        canvas.put_pixel_depth(p, 0.5, 0x111111, DepthMode::TEST_ONLY);
+        assert_eq!(canvas.get_pixel(p), Some(0x111111));
+        canvas.put_pixel_depth(p, 0.25, 0x222222, DepthMode::TEST_AND_WRITE);
+        assert_eq!(canvas.get_pixel(p), Some(0x222222));
+
+        // and is still rejected by what's nearer
+        canvas.put_pixel_depth(p, 0.1, 0x333333, DepthMode::TEST_ONLY);
+        assert_eq!(canvas.get_pixel(p), Some(0x222222));
+
+        // none ignores depth both ways
+        canvas.put_pixel_depth(p, 0.01, 0x444444, DepthMode::NONE);
+        assert_eq!(canvas.get_pixel(p), Some(0x444444));
+        assert_eq!(canvas.depth.as_deref().unwrap()[0], 0.25);
     }
 }
@@ -0,0 +1,105 @@
+// rgb in floating point, 0 to 1 meaning black to full brightness. math on
+// these doesn't clamp or round, so light can add up past 1 and come back down
+// without losing anything. only to_u32 squeezes it into a framebuffer pixel
+
+use std::ops::{Add, AddAssign, Div, Mul};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Color {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+}
+
+impl Color {
+    pub const BLACK: Color = Color::new(0.0, 0.0, 0.0);
+    pub const WHITE: Color = Color::new(1.0, 1.0, 1.0);
+
+    pub const fn new(r: f64, g: f64, b: f64) -> Self {
+        Self { r, g, b }
+    }
+
+    // from 0xRRGGBB
+    pub fn from_u32(color: u32) -> Self {
+        let channel = |shift: u32| ((color >> shift) & 0xFF) as f64 / 255.0;
+        Self::new(channel(16), channel(8), channel(0))
+    }
+
+    // to 0xRRGGBB, clamping anything out of range
+    pub fn to_u32(self) -> u32 {
+        let channel = |c: f64| (c.clamp(0.0, 1.0) * 255.0).round() as u32;
+        (channel(self.r) << 16) | (channel(self.g) << 8) | channel(self.b)
+    }
+
+    // self at t = 0, other at t = 1
+    pub fn lerp(self, other: Color, t: f64) -> Self {
+        self * (1.0 - t) + other * t
+    }
+}
+
+impl From<[f64; 3]> for Color {
+    fn from([r, g, b]: [f64; 3]) -> Self {
+        Self::new(r, g, b)
+    }
+}
+
+impl Add for Color {
+    type Output = Self;
+    fn add(self, other: Color) -> Self {
+        Self::new(self.r + other.r, self.g + other.g, self.b + other.b)
+    }
+}
+
+impl AddAssign for Color {
+    fn add_assign(&mut self, other: Color) {
+        *self = *self + other;
+    }
+}
+
+// channel by channel, e.g. light tinted by a surface
+impl Mul for Color {
+    type Output = Self;
+    fn mul(self, other: Color) -> Self {
+        Self::new(self.r * other.r, self.g * other.g, self.b * other.b)
+    }
+}
+
+impl Mul<f64> for Color {
+    type Output = Self;
+    fn mul(self, n: f64) -> Self {
+        Self::new(self.r * n, self.g * n, self.b * n)
+    }
+}
+
+impl Div<f64> for Color {
+    type Output = Self;
+    fn div(self, n: f64) -> Self {
+        Self::new(self.r / n, self.g / n, self.b / n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_u32() {
+        for packed in [0x000000, 0xFFFFFF, 0x123456, 0xFF8000] {
+            assert_eq!(Color::from_u32(packed).to_u32(), packed);
+        }
+    }
+
+    #[test]
+    fn keeps_what_u32_would_lose() {
+        // over-bright then scaled back down, packed u32 would have clipped at the first step
+        let bright = Color::from_u32(0x808080) * 4.0;
+        assert_eq!((bright * 0.25).to_u32(), 0x808080);
+
+        // and a fraction of a step survives being scaled back up
+        let dim = Color::from_u32(0x010101) * 0.4;
+        assert_eq!((dim * 2.5).to_u32(), 0x010101);
+
+        assert_eq!(Color::BLACK.lerp(Color::WHITE, 0.5).to_u32(), 0x808080);
+        assert_eq!(Color::WHITE.to_u32(), 0xFFFFFF);
+    }
+}
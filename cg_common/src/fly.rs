@@ -0,0 +1,278 @@
+// a first-person fly-through camera both viewers drive from their input
+// events. the calls below don't know about winit, and with the winit feature
+// on handle_window_event maps a window's keys, mouse and wheel onto them
+
+use std::f64::consts::FRAC_PI_2;
+
+use crate::math::{Matrix4, Point3D, Vector3};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Move {
+    Forward,
+    Back,
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Move {
+    const ALL: [Move; 6] = [Move::Forward, Move::Back, Move::Left, Move::Right, Move::Up, Move::Down];
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FlyCamera {
+    pub position: Point3D,
+    pub yaw: f64, // radians around +y, 0 looks down +z and positive turns right
+    pub pitch: f64, // radians, positive looks up
+    pub fov: f64, // vertical, radians
+    pub speed: f64, // units per second
+    pub sensitivity: f64, // radians per pixel of mouse movement
+    held: [bool; 6], // indexed like Move::ALL
+    home: (Point3D, f64, f64, f64), // position, yaw, pitch, fov for reset()
+    dragging: bool, // left mouse button is down
+    cursor: Option<(f64, f64)>, // last known, in window pixels
+}
+
+const MAX_PITCH: f64 = FRAC_PI_2 - 0.01; // straight up or down flips the view
+const MIN_FOV: f64 = 0.1;
+const MAX_FOV: f64 = 2.6; // about 150 degrees
+
+impl FlyCamera {
+    pub fn new(position: Point3D, yaw: f64, pitch: f64, fov: f64) -> Self {
+        let pitch = pitch.clamp(-MAX_PITCH, MAX_PITCH);
+        let fov = fov.clamp(MIN_FOV, MAX_FOV);
+        Self {
+            position,
+            yaw,
+            pitch,
+            fov,
+            speed: 2.0,
+            sensitivity: 0.005,
+            held: [false; 6],
+            home: (position, yaw, pitch, fov),
+            dragging: false,
+            cursor: None,
+        }
+    }
+
+    // picks up where an existing camera -> world rotation was looking. any
+    // roll is lost, the fly camera always keeps +y up
+    pub fn from_orientation(position: Point3D, orientation: Matrix4, fov: f64) -> Self {
+        let forward = orientation.transform_vector(Vector3 { x: 0.0, y: 0.0, z: 1.0 }).normalize();
+        Self::new(position, forward.x.atan2(forward.z), forward.y.clamp(-1.0, 1.0).asin(), fov)
+    }
+
+    // camera -> world rotation
+    pub fn orientation(&self) -> Matrix4 {
+        Matrix4::rotation_y(self.yaw) * Matrix4::rotation_x(-self.pitch)
+    }
+
+    pub fn set_held(&mut self, movement: Move, held: bool) {
+        if let Some(i) = Move::ALL.iter().position(|m| *m == movement) {
+            self.held[i] = held;
+        }
+    }
+
+    // any movement key is down, so update() has work to do
+    pub fn is_moving(&self) -> bool {
+        self.held.iter().any(|h| *h)
+    }
+
+    // forget held keys and buttons, e.g. when the window loses focus and we'd miss the release
+    pub fn release_all(&mut self) {
+        self.held = [false; 6];
+        self.dragging = false;
+    }
+
+    // mouse moved by dx, dy pixels, +y down like window coordinates
+    pub fn look(&mut self, dx: f64, dy: f64) {
+        self.yaw += dx * self.sensitivity;
+        self.pitch = (self.pitch - dy * self.sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    // positive steps zoom in (narrower fov)
+    pub fn zoom(&mut self, steps: f64) {
+        self.fov = (self.fov * 0.9f64.powf(steps)).clamp(MIN_FOV, MAX_FOV);
+    }
+
+    // where reset() goes back to, taken from another camera's current view
+    pub fn set_home(&mut self, other: FlyCamera) {
+        self.home = (other.position, other.yaw, other.pitch, other.fov);
+    }
+
+    pub fn reset(&mut self) {
+        (self.position, self.yaw, self.pitch, self.fov) = self.home;
+    }
+
+    // moves along the view direction for dt seconds. true if it went anywhere
+    pub fn update(&mut self, dt: f64) -> bool {
+        let orientation = self.orientation();
+        let forward = orientation.transform_vector(Vector3 { x: 0.0, y: 0.0, z: 1.0 });
+        let right = orientation.transform_vector(Vector3 { x: 1.0, y: 0.0, z: 0.0 });
+        let up = Vector3 { x: 0.0, y: 1.0, z: 0.0 };
+
+        let mut direction = Vector3 { x: 0.0, y: 0.0, z: 0.0 };
+        for (movement, held) in Move::ALL.iter().zip(self.held) {
+            if !held {
+                continue;
+            }
+            direction = direction + match movement {
+                Move::Forward => forward,
+                Move::Back => -forward,
+                Move::Right => right,
+                Move::Left => -right,
+                Move::Up => up,
+                Move::Down => -up,
+            };
+        }
+
+        // opposite keys cancel out
+        if direction.len() < 1e-9 {
+            return false;
+        }
+        self.position = self.position + direction.normalize() * (self.speed * dt);
+        true
+    }
+}
+
+// what handle_window_event did with an event
+#[cfg(feature = "winit")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    Ignored, // not one of ours, the viewer can have it
+    Handled,
+    StartedMoving, // from standing still, so don't count the time since the last update()
+    ViewChanged, // looked, zoomed or reset. redraw
+}
+
+#[cfg(feature = "winit")]
+impl FlyCamera {
+    // WASD moves, Q and E go down and up, R goes back home, dragging with
+    // the left button looks around and the wheel zooms
+    pub fn handle_window_event(&mut self, event: &winit::event::WindowEvent) -> Input {
+        use winit::event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent};
+        use winit::keyboard::{KeyCode, PhysicalKey};
+
+        match event {
+            WindowEvent::KeyboardInput { event: KeyEvent { physical_key: PhysicalKey::Code(code), state, repeat: false, .. }, .. } => {
+                let pressed = *state == ElementState::Pressed;
+                if let Some(movement) = movement_key(*code) {
+                    let started = pressed && !self.is_moving();
+                    self.set_held(movement, pressed);
+                    return if started { Input::StartedMoving } else { Input::Handled };
+                }
+                if *code == KeyCode::KeyR {
+                    if pressed {
+                        self.reset();
+                        return Input::ViewChanged;
+                    }
+                    return Input::Handled;
+                }
+                Input::Ignored
+            },
+            WindowEvent::MouseInput { state, button: MouseButton::Left, .. } => {
+                self.dragging = *state == ElementState::Pressed;
+                Input::Handled
+            },
+            WindowEvent::CursorMoved { position, .. } => {
+                let (x, y) = (position.x, position.y);
+                let last = self.cursor.replace((x, y));
+                match last {
+                    Some((last_x, last_y)) if self.dragging => {
+                        self.look(x - last_x, y - last_y);
+                        Input::ViewChanged
+                    },
+                    _ => Input::Handled,
+                }
+            },
+            WindowEvent::MouseWheel { delta, .. } => {
+                let steps = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y as f64,
+                    MouseScrollDelta::PixelDelta(p) => p.y / 40.0,
+                };
+                self.zoom(steps);
+                Input::ViewChanged
+            },
+            WindowEvent::Focused(false) => {
+                // we won't hear about keys released while we're in the background
+                self.release_all();
+                Input::Handled
+            },
+            _ => Input::Ignored,
+        }
+    }
+}
+
+// by physical key, so it's the same place on any keyboard layout
+#[cfg(feature = "winit")]
+fn movement_key(code: winit::keyboard::KeyCode) -> Option<Move> {
+    use winit::keyboard::KeyCode;
+
+    match code {
+        KeyCode::KeyW => Some(Move::Forward),
+        KeyCode::KeyS => Some(Move::Back),
+        KeyCode::KeyA => Some(Move::Left),
+        KeyCode::KeyD => Some(Move::Right),
+        KeyCode::KeyE => Some(Move::Up),
+        KeyCode::KeyQ => Some(Move::Down),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn orientation_round_trips() {
+        let camera = FlyCamera::new(Point3D::new(0.0, 0.0, 0.0), 0.7, -0.3, 1.0);
+        let copy = FlyCamera::from_orientation(camera.position, camera.orientation(), camera.fov);
+
+        assert!(close(camera.yaw, copy.yaw));
+        assert!(close(camera.pitch, copy.pitch));
+    }
+
+    #[test]
+    fn moves_where_it_looks_and_resets() {
+        // turned a quarter right, so forward is +x
+        let mut camera = FlyCamera::new(Point3D::new(0.0, 0.0, 0.0), FRAC_PI_2, 0.0, 1.0);
+        camera.set_held(Move::Forward, true);
+        assert!(camera.update(0.5));
+        assert!(close(camera.position.x, 1.0) && close(camera.position.z, 0.0));
+
+        camera.set_held(Move::Back, true);
+        assert!(!camera.update(0.5));
+
+        camera.reset();
+        assert!(close(camera.position.x, 0.0));
+    }
+
+    #[cfg(feature = "winit")]
+    #[test]
+    fn losing_focus_lets_go_of_everything() {
+        use winit::event::WindowEvent;
+
+        let mut camera = FlyCamera::new(Point3D::new(0.0, 0.0, 0.0), 0.0, 0.0, 1.0);
+        camera.set_held(Move::Forward, true);
+        camera.dragging = true;
+
+        assert_eq!(camera.handle_window_event(&WindowEvent::Focused(false)), Input::Handled);
+        assert!(!camera.is_moving() && !camera.dragging);
+        assert_eq!(camera.handle_window_event(&WindowEvent::Focused(true)), Input::Ignored);
+    }
+
+    #[cfg(feature = "winit")]
+    #[test]
+    fn wasd_eq_by_physical_key() {
+        use winit::keyboard::KeyCode;
+
+        assert_eq!(movement_key(KeyCode::KeyW), Some(Move::Forward));
+        assert_eq!(movement_key(KeyCode::KeyQ), Some(Move::Down));
+        assert_eq!(movement_key(KeyCode::KeyR), None);
+    }
+}
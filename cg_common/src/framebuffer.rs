@@ -0,0 +1,180 @@
+use std::io;
+use std::path::Path;
+
+use crate::canvas::Canvas;
+use crate::image;
+
+// owns its pixels, so it can live across frames and render without a window
+pub struct Framebuffer {
+    pub color: Vec<u32>,
+    pub depth: Option<Vec<f64>>,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            color: vec![0; width as usize * height as usize],
+            depth: None,
+            width,
+            height,
+        }
+    }
+
+    pub fn with_depth(width: u32, height: u32) -> Self {
+        Self {
+            depth: Some(vec![0.0; width as usize * height as usize]),
+            ..Self::new(width, height)
+        }
+    }
+
+    // no-op if the size hasn't changed. contents are cleared otherwise
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == self.width && height == self.height {
+            return;
+        }
+
+        let len = width as usize * height as usize;
+        self.color = vec![0; len];
+        if let Some(depth) = &mut self.depth {
+            *depth = vec![0.0; len];
+        }
+        self.width = width;
+        self.height = height;
+    }
+
+    pub fn clear(&mut self, color: u32) {
+        self.color.fill(color);
+        if let Some(depth) = &mut self.depth {
+            depth.fill(0.0);
+        }
+    }
+
+    pub fn canvas(&mut self) -> Canvas<'_> {
+        Canvas {
+            buffer: &mut self.color,
+            depth: self.depth.as_deref_mut(),
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    // copy into a window surface (e.g. a softbuffer Buffer) that is
+    // target_width pixels wide. anything that doesn't overlap is left alone
+    pub fn blit(&self, target: &mut [u32], target_width: u32, target_height: u32) {
+        let w = self.width.min(target_width) as usize;
+        let h = self.height.min(target_height) as usize;
+
+        for y in 0..h {
+            let src = y * self.width as usize;
+            let dst = y * target_width as usize;
+            if dst + w > target.len() {
+                break;
+            }
+            target[dst..dst + w].copy_from_slice(&self.color[src..src + w]);
+        }
+    }
+
+    // like blit, but stretched (nearest neighbour) to cover the whole target.
+    // for showing a low resolution preview in a full size window
+    pub fn blit_scaled(&self, target: &mut [u32], target_width: u32, target_height: u32) {
+        if self.width == 0 || self.height == 0 {
+            return;
+        }
+
+        for y in 0..target_height as usize {
+            let src_y = y * self.height as usize / target_height as usize;
+            let src = &self.color[src_y * self.width as usize..(src_y + 1) * self.width as usize];
+            let dst = y * target_width as usize;
+            let Some(row) = target.get_mut(dst..dst + target_width as usize) else {
+                break;
+            };
+
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = src[x * self.width as usize / target_width as usize];
+            }
+        }
+    }
+
+    // format comes from the extension: .png, .bmp or .ppm
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        image::save(path, &self.color, self.width, self.height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resize_keeps_depth_in_step() {
+        let mut framebuffer = Framebuffer::with_depth(2, 2);
+        framebuffer.clear(0xFF0000);
+        framebuffer.depth.as_mut().unwrap()[0] = 0.5;
+
+        framebuffer.resize(2, 2);
+        assert_eq!(framebuffer.color[0], 0xFF0000); // same size, left alone
+        assert_eq!(framebuffer.depth.as_ref().unwrap()[0], 0.5);
+
+        framebuffer.resize(3, 5);
+        assert_eq!((framebuffer.width, framebuffer.height), (3, 5));
+        assert_eq!(framebuffer.color, vec![0; 15]);
+        assert_eq!(framebuffer.depth.as_deref(), Some(&[0.0; 15][..]));
+
+        let mut flat = Framebuffer::new(2, 2);
+        flat.resize(4, 4);
+        assert_eq!(flat.color.len(), 16);
+        assert!(flat.depth.is_none());
+    }
+
+    fn numbered(width: u32, height: u32) -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(width, height);
+        for (i, pixel) in framebuffer.color.iter_mut().enumerate() {
+            *pixel = i as u32 + 1;
+        }
+        framebuffer
+    }
+
+    #[test]
+    fn blit_only_copies_the_overlap() {
+        let framebuffer = numbered(3, 2);
+
+        // a bigger target keeps what's outside
+        let mut target = vec![0; 4 * 3];
+        framebuffer.blit(&mut target, 4, 3);
+        assert_eq!(target, [1, 2, 3, 0, 4, 5, 6, 0, 0, 0, 0, 0]);
+
+        // a smaller one gets the top left corner
+        let mut target = vec![0; 2];
+        framebuffer.blit(&mut target, 2, 1);
+        assert_eq!(target, [1, 2]);
+
+        // and one shorter than it says it is stops instead of panicking
+        let mut target = vec![0; 5];
+        framebuffer.blit(&mut target, 3, 2);
+        assert_eq!(target, [1, 2, 3, 0, 0]);
+    }
+
+    #[test]
+    fn blit_scaled_covers_the_target() {
+        let framebuffer = numbered(2, 2);
+
+        let mut target = vec![0; 4 * 4];
+        framebuffer.blit_scaled(&mut target, 4, 4);
+        assert_eq!(target, [1, 1, 2, 2, 1, 1, 2, 2, 3, 3, 4, 4, 3, 3, 4, 4]);
+
+        let mut target = vec![0; 1];
+        framebuffer.blit_scaled(&mut target, 1, 1);
+        assert_eq!(target, [1]);
+
+        let mut target = vec![0; 6];
+        framebuffer.blit_scaled(&mut target, 4, 4);
+        assert_eq!(target, [1, 1, 2, 2, 0, 0]);
+
+        // nothing to stretch
+        let mut target = vec![7; 4];
+        Framebuffer::new(0, 0).blit_scaled(&mut target, 2, 2);
+        assert_eq!(target, [7; 4]);
+    }
+}
@@ -0,0 +1,228 @@
+// dependency-free encoders for getting pixels out of a Canvas or Framebuffer.
+// pixels are the usual packed 0xRRGGBB, rows top to bottom
+
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Ppm,
+    Bmp,
+    Png,
+}
+
+impl ImageFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            "bmp" => Some(ImageFormat::Bmp),
+            "png" => Some(ImageFormat::Png),
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self, pixels: &[u32], width: u32, height: u32) -> io::Result<Vec<u8>> {
+        match self {
+            ImageFormat::Ppm => encode_ppm(pixels, width, height),
+            ImageFormat::Bmp => encode_bmp(pixels, width, height),
+            ImageFormat::Png => encode_png(pixels, width, height),
+        }
+    }
+}
+
+// picks the format from the file extension
+pub fn save(path: &Path, pixels: &[u32], width: u32, height: u32) -> io::Result<()> {
+    let format = ImageFormat::from_path(path).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("don't know how to write {} (expected .png, .bmp or .ppm)", path.display()),
+        )
+    })?;
+
+    std::fs::write(path, format.encode(pixels, width, height)?)
+}
+
+// there have to be at least width * height pixels. extra ones past that are ignored
+fn check_size(pixels: &[u32], width: u32, height: u32) -> io::Result<()> {
+    let needed = width as usize * height as usize;
+    if pixels.len() < needed {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("a {width}x{height} image needs {needed} pixels, got {}", pixels.len()),
+        ));
+    }
+    Ok(())
+}
+
+fn rgb(pixel: u32) -> [u8; 3] {
+    [(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8]
+}
+
+pub fn encode_ppm(pixels: &[u32], width: u32, height: u32) -> io::Result<Vec<u8>> {
+    check_size(pixels, width, height)?;
+
+    let mut out = format!("P6\n{width} {height}\n255\n").into_bytes();
+    for pixel in &pixels[..width as usize * height as usize] {
+        out.extend_from_slice(&rgb(*pixel));
+    }
+    Ok(out)
+}
+
+pub fn encode_bmp(pixels: &[u32], width: u32, height: u32) -> io::Result<Vec<u8>> {
+    check_size(pixels, width, height)?;
+
+    let row_size = (width as usize * 3).div_ceil(4) * 4; // rows are padded to 4 bytes
+    let image_size = row_size * height as usize;
+    let offset = 14 + 40;
+
+    let mut out = Vec::with_capacity(offset + image_size);
+
+    // BITMAPFILEHEADER
+    out.extend_from_slice(b"BM");
+    out.extend_from_slice(&((offset + image_size) as u32).to_le_bytes());
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(&(offset as u32).to_le_bytes());
+
+    // BITMAPINFOHEADER
+    out.extend_from_slice(&40u32.to_le_bytes());
+    out.extend_from_slice(&(width as i32).to_le_bytes());
+    out.extend_from_slice(&(height as i32).to_le_bytes()); // positive = bottom-up
+    out.extend_from_slice(&1u16.to_le_bytes()); // planes
+    out.extend_from_slice(&24u16.to_le_bytes()); // bits per pixel
+    out.extend_from_slice(&0u32.to_le_bytes()); // BI_RGB
+    out.extend_from_slice(&(image_size as u32).to_le_bytes());
+    out.extend_from_slice(&2835i32.to_le_bytes()); // 72 dpi
+    out.extend_from_slice(&2835i32.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+
+    for y in (0..height as usize).rev() {
+        let row = &pixels[y * width as usize..(y + 1) * width as usize];
+        let start = out.len();
+        for pixel in row {
+            let [r, g, b] = rgb(*pixel);
+            out.extend_from_slice(&[b, g, r]);
+        }
+        out.resize(start + row_size, 0);
+    }
+
+    Ok(out)
+}
+
+// 8-bit RGB, no filtering, and the zlib stream uses stored (uncompressed)
+// deflate blocks. big files, but any viewer opens them and there's no zlib dep
+pub fn encode_png(pixels: &[u32], width: u32, height: u32) -> io::Result<Vec<u8>> {
+    check_size(pixels, width, height)?;
+
+    let mut raw = Vec::with_capacity((width as usize * 3 + 1) * height as usize);
+    for y in 0..height as usize {
+        raw.push(0); // filter type: none
+        for pixel in &pixels[y * width as usize..(y + 1) * width as usize] {
+            raw.extend_from_slice(&rgb(*pixel));
+        }
+    }
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]); // bit depth, truecolor, deflate, no filter, no interlace
+
+    let mut out = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+    png_chunk(&mut out, b"IHDR", &ihdr);
+    png_chunk(&mut out, b"IDAT", &zlib_stored(&raw));
+    png_chunk(&mut out, b"IEND", &[]);
+    Ok(out)
+}
+
+fn png_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01]; // deflate, 32k window, no preset dictionary
+
+    let mut blocks = data.chunks(0xFFFF).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]); // a single empty final block
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        out.push(last as u8); // BFINAL, BTYPE = 00
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) { // largest n that can't overflow before the modulo
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn png_layout() {
+        let png = encode_png(&[0xFF0000, 0x00FF00, 0x0000FF, 0xFFFFFF], 2, 2).unwrap();
+
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[12..16], b"IHDR");
+        // IEND is always the same 12 bytes
+        assert_eq!(&png[png.len() - 12..], b"\0\0\0\0IEND\xAE\x42\x60\x82");
+    }
+
+    #[test]
+    fn bmp_rows_are_padded() {
+        let bmp = encode_bmp(&[0x010203; 3], 3, 1).unwrap();
+
+        assert_eq!(bmp.len(), 54 + 12);
+        assert_eq!(&bmp[54..57], &[3, 2, 1]);
+    }
+
+    #[test]
+    fn short_buffers_are_an_error() {
+        for format in [ImageFormat::Ppm, ImageFormat::Bmp, ImageFormat::Png] {
+            let err = format.encode(&[0; 5], 2, 3).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{format:?}");
+            assert!(format.encode(&[0; 6], 2, 3).is_ok());
+        }
+    }
+}
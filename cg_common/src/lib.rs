@@ -1,3 +1,7 @@
 pub mod math;
+pub mod color;
 pub mod canvas;
-
+pub mod framebuffer;
+pub mod image;
+pub mod obj;
+pub mod fly;
@@ -13,10 +13,18 @@ impl Point2D {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
 pub struct ShadedVertex2 {
     pub x: f64,
     pub y: f64,
     pub h: f64,
+    pub inv_z: f64, // 1/z, for the depth buffer
+}
+
+impl From<ShadedVertex2> for Point2D {
+    fn from(v: ShadedVertex2) -> Self {
+        Point2D { x: v.x, y: v.y }
+    }
 }
 
 pub struct Vertex3 {
@@ -40,6 +48,18 @@ impl Point3D {
     pub fn project2d(&self) -> Point2D {
         Point2D { x: self.x, y: self.y }
     }
+
+    // the inverse of Point2D::project_viewport: perspective-divide onto the
+    // projection plane at `distance`, then scale viewport units to canvas pixels
+    pub fn project_canvas(&self, viewport_w: f64, viewport_h: f64, canvas_w: u32, canvas_h: u32, distance: f64) -> Point2D {
+        let vx = self.x * distance / self.z;
+        let vy = self.y * distance / self.z;
+
+        Point2D {
+            x: vx * (canvas_w as f64 / viewport_w),
+            y: vy * (canvas_h as f64 / viewport_h),
+        }
+    }
 }
 
 impl std::ops::Sub for Point3D {
@@ -185,6 +205,109 @@ pub fn bad_quadratic(a: f64, b:f64, c: f64) -> Option<(f64, f64)> {
     }
 }
 
+// real roots of a x^3 + b x^2 + c x + d, ascending. repeated roots show up once
+pub fn solve_cubic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    if a == 0.0 {
+        return match bad_quadratic(b, c, d) {
+            Some((r1, r2)) if b != 0.0 && r1 != r2 => vec![r1, r2],
+            Some((r1, _)) if b != 0.0 => vec![r1],
+            _ if c != 0.0 => vec![-d / c],
+            _ => Vec::new(),
+        };
+    }
+
+    // depressed cubic t^3 + p t + q with x = t - b / 3a
+    let (b, c, d) = (b / a, c / a, d / a);
+    let shift = b / 3.0;
+    let p = c - b * b / 3.0;
+    let q = 2.0 * b * b * b / 27.0 - b * c / 3.0 + d;
+
+    let discriminant = (q / 2.0).powi(2) + (p / 3.0).powi(3);
+    let mut roots = if discriminant > 1e-14 {
+        let sqrt_dis = discriminant.sqrt();
+        vec![(-q / 2.0 + sqrt_dis).cbrt() + (-q / 2.0 - sqrt_dis).cbrt()]
+    } else if discriminant.abs() <= 1e-14 {
+        let u = (-q / 2.0).cbrt();
+        if u == 0.0 { vec![0.0] } else { vec![2.0 * u, -u] }
+    } else {
+        // three real roots, trigonometric form
+        let r = (-p / 3.0).sqrt();
+        let phi = (-q / (2.0 * r * r * r)).clamp(-1.0, 1.0).acos();
+        (0..3).map(|k| 2.0 * r * ((phi + std::f64::consts::TAU * k as f64) / 3.0).cos()).collect()
+    };
+
+    for root in roots.iter_mut() {
+        *root -= shift;
+    }
+    roots.sort_by(|a, b| a.total_cmp(b));
+    roots.dedup_by(|a, b| (*a - *b).abs() < 1e-12);
+    roots
+}
+
+// real roots of a x^4 + b x^3 + c x^2 + d x + e, ascending. Ferrari's method,
+// then a few Newton steps on the original polynomial to win back the
+// precision the closed form loses (which matters a lot for tori)
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    if a == 0.0 {
+        return solve_cubic(b, c, d, e);
+    }
+
+    // depressed quartic y^4 + p y^2 + q y + r with x = y - b / 4a
+    let (b, c, d, e) = (b / a, c / a, d / a, e / a);
+    let shift = b / 4.0;
+    let p = c - 3.0 * b * b / 8.0;
+    let q = d - b * c / 2.0 + b * b * b / 8.0;
+    let r = e - b * d / 4.0 + b * b * c / 16.0 - 3.0 * b * b * b * b / 256.0;
+
+    let mut ys: Vec<f64> = Vec::new();
+    if q.abs() < 1e-12 {
+        // biquadratic: a quadratic in y^2
+        if let Some((z1, z2)) = bad_quadratic(1.0, p, r) {
+            for z in [z1, z2] {
+                if z >= 0.0 {
+                    ys.push(z.sqrt());
+                    ys.push(-z.sqrt());
+                }
+            }
+        }
+    } else {
+        // any positive root of the resolvent cubic splits it into two quadratics
+        let z = solve_cubic(1.0, 2.0 * p, p * p - 4.0 * r, -q * q)
+            .into_iter()
+            .fold(f64::NEG_INFINITY, f64::max);
+        if z > 0.0 {
+            let s = z.sqrt();
+            for (k, m) in [(s, (p + z) / 2.0 - q / (2.0 * s)), (-s, (p + z) / 2.0 + q / (2.0 * s))] {
+                if let Some((y1, y2)) = bad_quadratic(1.0, k, m) {
+                    ys.push(y1);
+                    ys.push(y2);
+                }
+            }
+        }
+    }
+
+    let f = |x: f64| (((x + b) * x + c) * x + d) * x + e;
+    let df = |x: f64| ((4.0 * x + 3.0 * b) * x + 2.0 * c) * x + d;
+
+    let mut roots: Vec<f64> = ys.into_iter()
+        .map(|y| {
+            let mut x = y - shift;
+            for _ in 0..3 {
+                let slope = df(x);
+                if slope == 0.0 {
+                    break;
+                }
+                x -= f(x) / slope;
+            }
+            x
+        })
+        .collect();
+
+    roots.sort_by(|a, b| a.total_cmp(b));
+    roots.dedup_by(|a, b| (*a - *b).abs() < 1e-9);
+    roots
+}
+
 pub fn lerp(i0: f64, d0: f64, i1: f64, d1: f64) -> Vec<i32> {
     let mut values: Vec<i32> = Vec::new();
 
@@ -229,13 +352,425 @@ pub fn apply_intensity(color: u32, n: f64) -> u32 {
     let green = (((color >> 8) & 0xFF) as f64 * n) as u8;
     let blue = (((color) & 0xFF) as f64 * n) as u8;
 
-    (((red.clamp(0, 255) as u32) << 16) | 
-    ((green.clamp(0, 255) as u32) << 8) | 
-    (blue.clamp(0, 255) as u32)).into()
+    ((red as u32) << 16) | 
+    ((green as u32) << 8) | 
+    (blue as u32)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix4 {
+    pub m: [[f64; 4]; 4], // row-major, m[row][col]
+}
+
+impl Matrix4 {
+    pub fn new(m: [[f64; 4]; 4]) -> Self {
+        Self { m }
+    }
+
+    pub fn identity() -> Self {
+        Self::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn translation(t: Vector3) -> Self {
+        Self::new([
+            [1.0, 0.0, 0.0, t.x],
+            [0.0, 1.0, 0.0, t.y],
+            [0.0, 0.0, 1.0, t.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn scale(s: Vector3) -> Self {
+        Self::new([
+            [s.x, 0.0, 0.0, 0.0],
+            [0.0, s.y, 0.0, 0.0],
+            [0.0, 0.0, s.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn uniform_scale(s: f64) -> Self {
+        Self::scale(Vector3 { x: s, y: s, z: s })
+    }
+
+    // all angles are in radians
+    pub fn rotation_x(angle: f64) -> Self {
+        let (s, c) = angle.sin_cos();
+        Self::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0,   c,  -s, 0.0],
+            [0.0,   s,   c, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn rotation_y(angle: f64) -> Self {
+        let (s, c) = angle.sin_cos();
+        Self::new([
+            [  c, 0.0,   s, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [ -s, 0.0,   c, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn rotation_z(angle: f64) -> Self {
+        let (s, c) = angle.sin_cos();
+        Self::new([
+            [  c,  -s, 0.0, 0.0],
+            [  s,   c, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // rotation about an arbitrary axis (Rodrigues)
+    pub fn rotation(axis: Vector3, angle: f64) -> Self {
+        let a = axis.normalize();
+        let (s, c) = angle.sin_cos();
+        let t = 1.0 - c;
+        Self::new([
+            [t * a.x * a.x + c,       t * a.x * a.y - s * a.z, t * a.x * a.z + s * a.y, 0.0],
+            [t * a.x * a.y + s * a.z, t * a.y * a.y + c,       t * a.y * a.z - s * a.x, 0.0],
+            [t * a.x * a.z - s * a.y, t * a.y * a.z + s * a.x, t * a.z * a.z + c,       0.0],
+            [0.0,                     0.0,                     0.0,                     1.0],
+        ])
+    }
+
+    // world -> camera matrix. like the book, the camera looks down +z with +y up
+    pub fn look_at(eye: Point3D, target: Point3D, up: Vector3) -> Self {
+        let forward = (target - eye).normalize();
+        let right = up.cross(forward).normalize();
+        let true_up = forward.cross(right);
+        let e = eye - Point3D::new(0.0, 0.0, 0.0);
+
+        Self::new([
+            [right.x,   right.y,   right.z,   -right.dot(e)],
+            [true_up.x, true_up.y, true_up.z, -true_up.dot(e)],
+            [forward.x, forward.y, forward.z, -forward.dot(e)],
+            [0.0,       0.0,       0.0,       1.0],
+        ])
+    }
+
+    // the rotation that turns a camera at `eye` to face `target`, camera -> world.
+    // look_at without the translation, transposed back the other way
+    pub fn look_rotation(eye: Point3D, target: Point3D, up: Vector3) -> Self {
+        let mut view = Self::look_at(eye, target, up);
+        for row in view.m.iter_mut().take(3) {
+            row[3] = 0.0;
+        }
+        view.transpose()
+    }
+
+    // camera space -> clip space, w ends up holding the camera z.
+    // after the divide, x, y and z of everything inside the frustum land in [-1, 1]
+    pub fn perspective(fov_y: f64, aspect: f64, near: f64, far: f64) -> Self {
+        let f = 1.0 / (fov_y / 2.0).tan();
+        let range = far - near;
+        Self::new([
+            [f / aspect, 0.0, 0.0,                  0.0],
+            [0.0,        f,   0.0,                  0.0],
+            [0.0,        0.0, (far + near) / range, -2.0 * far * near / range],
+            [0.0,        0.0, 1.0,                  0.0],
+        ])
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut out = [[0.0; 4]; 4];
+        for (r, row) in out.iter_mut().enumerate() {
+            for (c, v) in row.iter_mut().enumerate() {
+                *v = self.m[c][r];
+            }
+        }
+        Self::new(out)
+    }
+
+    pub fn determinant(&self) -> f64 {
+        let (s, c) = self.minors();
+        s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0]
+    }
+
+    // 2x2 sub-determinants of the top two and bottom two rows, shared by
+    // determinant() and inverse()
+    fn minors(&self) -> ([f64; 6], [f64; 6]) {
+        let m = &self.m;
+        let s = [
+            m[0][0] * m[1][1] - m[1][0] * m[0][1],
+            m[0][0] * m[1][2] - m[1][0] * m[0][2],
+            m[0][0] * m[1][3] - m[1][0] * m[0][3],
+            m[0][1] * m[1][2] - m[1][1] * m[0][2],
+            m[0][1] * m[1][3] - m[1][1] * m[0][3],
+            m[0][2] * m[1][3] - m[1][2] * m[0][3],
+        ];
+        let c = [
+            m[2][0] * m[3][1] - m[3][0] * m[2][1],
+            m[2][0] * m[3][2] - m[3][0] * m[2][2],
+            m[2][0] * m[3][3] - m[3][0] * m[2][3],
+            m[2][1] * m[3][2] - m[3][1] * m[2][2],
+            m[2][1] * m[3][3] - m[3][1] * m[2][3],
+            m[2][2] * m[3][3] - m[3][2] * m[2][3],
+        ];
+        (s, c)
+    }
+
+    // None if the matrix is singular
+    pub fn inverse(&self) -> Option<Self> {
+        let m = &self.m;
+        let (s, c) = self.minors();
+        let det = s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0];
+
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let inv = 1.0 / det;
+
+        Some(Self::new([
+            [
+                ( m[1][1] * c[5] - m[1][2] * c[4] + m[1][3] * c[3]) * inv,
+                (-m[0][1] * c[5] + m[0][2] * c[4] - m[0][3] * c[3]) * inv,
+                ( m[3][1] * s[5] - m[3][2] * s[4] + m[3][3] * s[3]) * inv,
+                (-m[2][1] * s[5] + m[2][2] * s[4] - m[2][3] * s[3]) * inv,
+            ],
+            [
+                (-m[1][0] * c[5] + m[1][2] * c[2] - m[1][3] * c[1]) * inv,
+                ( m[0][0] * c[5] - m[0][2] * c[2] + m[0][3] * c[1]) * inv,
+                (-m[3][0] * s[5] + m[3][2] * s[2] - m[3][3] * s[1]) * inv,
+                ( m[2][0] * s[5] - m[2][2] * s[2] + m[2][3] * s[1]) * inv,
+            ],
+            [
+                ( m[1][0] * c[4] - m[1][1] * c[2] + m[1][3] * c[0]) * inv,
+                (-m[0][0] * c[4] + m[0][1] * c[2] - m[0][3] * c[0]) * inv,
+                ( m[3][0] * s[4] - m[3][1] * s[2] + m[3][3] * s[0]) * inv,
+                (-m[2][0] * s[4] + m[2][1] * s[2] - m[2][3] * s[0]) * inv,
+            ],
+            [
+                (-m[1][0] * c[3] + m[1][1] * c[1] - m[1][2] * c[0]) * inv,
+                ( m[0][0] * c[3] - m[0][1] * c[1] + m[0][2] * c[0]) * inv,
+                (-m[3][0] * s[3] + m[3][1] * s[1] - m[3][2] * s[0]) * inv,
+                ( m[2][0] * s[3] - m[2][1] * s[1] + m[2][2] * s[0]) * inv,
+            ],
+        ]))
+    }
+
+    pub fn transform_homogeneous(&self, v: [f64; 4]) -> [f64; 4] {
+        let mut out = [0.0; 4];
+        for (r, o) in out.iter_mut().enumerate() {
+            *o = self.m[r][0] * v[0] + self.m[r][1] * v[1] + self.m[r][2] * v[2] + self.m[r][3] * v[3];
+        }
+        out
+    }
+
+    // points pick up translation (w = 1), and get divided through by w
+    // if the matrix is projective
+    pub fn transform_point(&self, p: Point3D) -> Point3D {
+        let [x, y, z, w] = self.transform_homogeneous([p.x, p.y, p.z, 1.0]);
+        if w != 0.0 && w != 1.0 {
+            Point3D::new(x / w, y / w, z / w)
+        } else {
+            Point3D::new(x, y, z)
+        }
+    }
+
+    // directions ignore translation (w = 0)
+    pub fn transform_vector(&self, v: Vector3) -> Vector3 {
+        let [x, y, z, _] = self.transform_homogeneous([v.x, v.y, v.z, 0.0]);
+        Vector3 { x, y, z }
+    }
+}
+
+impl Default for Matrix4 {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl std::ops::Mul for Matrix4 {
+    type Output = Self;
+
+    fn mul(self, other: Matrix4) -> Self {
+        let mut out = [[0.0; 4]; 4];
+        for (r, row) in out.iter_mut().enumerate() {
+            for (c, v) in row.iter_mut().enumerate() {
+                *v = (0..4).map(|k| self.m[r][k] * other.m[k][c]).sum();
+            }
+        }
+        Self::new(out)
+    }
+}
+
+// OpenGL-style matrix stack. transforms are applied to the top of the stack
+// on the right, so the last one you push is the first one applied to a vertex
+pub struct TransformStack {
+    stack: Vec<Matrix4>,
+}
+
+impl TransformStack {
+    pub fn new() -> Self {
+        Self { stack: vec![Matrix4::identity()] }
+    }
+
+    pub fn top(&self) -> Matrix4 {
+        *self.stack.last().unwrap()
+    }
+
+    pub fn push(&mut self) {
+        let top = self.top();
+        self.stack.push(top);
+    }
+
+    // the bottom matrix is never popped
+    pub fn pop(&mut self) -> Option<Matrix4> {
+        if self.stack.len() > 1 {
+            self.stack.pop()
+        } else {
+            None
+        }
+    }
+
+    pub fn apply(&mut self, m: Matrix4) {
+        let top = self.stack.last_mut().unwrap();
+        *top = *top * m;
+    }
+
+    pub fn translate(&mut self, t: Vector3) {
+        self.apply(Matrix4::translation(t));
+    }
+
+    pub fn rotate(&mut self, axis: Vector3, angle: f64) {
+        self.apply(Matrix4::rotation(axis, angle));
+    }
+
+    pub fn scale(&mut self, s: Vector3) {
+        self.apply(Matrix4::scale(s));
+    }
+
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+}
+
+impl Default for TransformStack {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn inverse_round_trip() {
+        let m = Matrix4::translation(Vector3 { x: 1.0, y: -2.0, z: 3.0 })
+            * Matrix4::rotation(Vector3 { x: 1.0, y: 1.0, z: 0.0 }, 0.7)
+            * Matrix4::scale(Vector3 { x: 2.0, y: 3.0, z: 0.5 });
+        let id = m * m.inverse().unwrap();
+
+        for r in 0..4 {
+            for c in 0..4 {
+                assert!(close(id.m[r][c], Matrix4::identity().m[r][c]));
+            }
+        }
+        assert!(Matrix4::scale(Vector3 { x: 1.0, y: 0.0, z: 1.0 }).inverse().is_none());
+    }
+
+    #[test]
+    fn points_translate_vectors_dont() {
+        let m = Matrix4::translation(Vector3 { x: 1.0, y: 2.0, z: 3.0 });
+        let p = m.transform_point(Point3D::new(1.0, 1.0, 1.0));
+        let v = m.transform_vector(Vector3 { x: 1.0, y: 1.0, z: 1.0 });
+
+        assert!(close(p.x, 2.0) && close(p.y, 3.0) && close(p.z, 4.0));
+        assert!(close(v.x, 1.0) && close(v.y, 1.0) && close(v.z, 1.0));
+    }
+
+    #[test]
+    fn look_at_puts_target_on_z_axis() {
+        let eye = Point3D::new(3.0, 1.0, -2.0);
+        let target = Point3D::new(0.0, 0.0, 5.0);
+        let view = Matrix4::look_at(eye, target, Vector3 { x: 0.0, y: 1.0, z: 0.0 });
+
+        let e = view.transform_point(eye);
+        let t = view.transform_point(target);
+        assert!(close(e.x, 0.0) && close(e.y, 0.0) && close(e.z, 0.0));
+        assert!(close(t.x, 0.0) && close(t.y, 0.0) && close(t.z, (target - eye).len()));
+    }
+
+    #[test]
+    fn look_rotation_turns_z_towards_the_target() {
+        let eye = Point3D::new(3.0, 1.0, -2.0);
+        let target = Point3D::new(0.0, 0.0, 5.0);
+        let rotation = Matrix4::look_rotation(eye, target, Vector3 { x: 0.0, y: 1.0, z: 0.0 });
+
+        let forward = rotation.transform_vector(Vector3 { x: 0.0, y: 0.0, z: 1.0 });
+        let expected = (target - eye).normalize();
+        assert!(close(forward.x, expected.x) && close(forward.y, expected.y) && close(forward.z, expected.z));
+
+        // nothing but a rotation, so the origin stays put
+        let o = rotation.transform_point(Point3D::new(0.0, 0.0, 0.0));
+        assert!(close(o.x, 0.0) && close(o.y, 0.0) && close(o.z, 0.0));
+    }
+
+    #[test]
+    fn project_canvas_inverts_project_viewport() {
+        let p = Point2D { x: -120.0, y: 45.0 };
+        let v = p.project_viewport(1.0, 0.75, 600, 450, 1.0);
+        let back = Point3D::new(v.x * 3.0, v.y * 3.0, v.z * 3.0).project_canvas(1.0, 0.75, 600, 450, 1.0);
+
+        assert!(close(back.x, p.x) && close(back.y, p.y));
+    }
+
+    #[test]
+    fn cubic_roots() {
+        // (x - 1)(x + 2)(x - 3)
+        let roots = solve_cubic(1.0, -2.0, -5.0, 6.0);
+        assert_eq!(roots.len(), 3);
+        for (r, expected) in roots.iter().zip([-2.0, 1.0, 3.0]) {
+            assert!(close(*r, expected));
+        }
+        // x^3 - 8, one real root
+        let roots = solve_cubic(1.0, 0.0, 0.0, -8.0);
+        assert!(roots.len() == 1 && close(roots[0], 2.0));
+    }
+
+    #[test]
+    fn quartic_roots() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        let roots = solve_quartic(1.0, -10.0, 35.0, -50.0, 24.0);
+        assert_eq!(roots.len(), 4);
+        for (r, expected) in roots.iter().zip([1.0, 2.0, 3.0, 4.0]) {
+            assert!(close(*r, expected));
+        }
+
+        // 2 (x^2 + 1)(x - 2)(x + 3): only two real roots
+        let roots = solve_quartic(2.0, 2.0, -10.0, 2.0, -12.0);
+        assert_eq!(roots.len(), 2);
+        assert!(close(roots[0], -3.0) && close(roots[1], 2.0));
+
+        // x^4 - 5x^2 + 4, biquadratic
+        let roots = solve_quartic(1.0, 0.0, -5.0, 0.0, 4.0);
+        assert_eq!(roots.len(), 4);
+        assert!(close(roots[0], -2.0) && close(roots[3], 2.0));
+    }
+
+    #[test]
+    fn transform_stack_push_pop() {
+        let mut stack = TransformStack::new();
+        stack.push();
+        stack.translate(Vector3 { x: 0.0, y: 0.0, z: 5.0 });
+        assert!(close(stack.top().transform_point(Point3D::new(0.0, 0.0, 0.0)).z, 5.0));
+
+        stack.pop();
+        assert_eq!(stack.top(), Matrix4::identity());
+        assert!(stack.pop().is_none());
+    }
 }
@@ -0,0 +1,335 @@
+// Wavefront .obj (and just enough .mtl) loading, shared by both renderers.
+//
+// we're left-handed (+z into the screen) and OBJ is right-handed, but the
+// numbers work out the same: an OBJ face's (b - a) x (c - a) points out of
+// the model, which is exactly what the rasterizer's Winding::Clockwise and
+// the raytracer's triangles expect. nothing gets flipped, the model just
+// comes out mirrored relative to other tools
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::math::{Point3D, Vector3};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io { path: PathBuf, source: std::io::Error },
+    Parse { file: Option<PathBuf>, line: usize, message: String },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io { path, source } => write!(f, "couldn't read {}: {source}", path.display()),
+            ObjError::Parse { file: Some(file), line, message } => write!(f, "{}:{line}: {message}", file.display()),
+            ObjError::Parse { file: None, line, message } => write!(f, "line {line}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ObjError::Io { source, .. } => Some(source),
+            ObjError::Parse { .. } => None,
+        }
+    }
+}
+
+impl ObjError {
+    fn in_file(self, path: &Path) -> Self {
+        match self {
+            ObjError::Parse { file: None, line, message } => ObjError::Parse { file: Some(path.to_path_buf()), line, message },
+            e => e,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Material {
+    pub name: String,
+    pub diffuse: [f64; 3], // Kd, 0..1
+    pub specular: [f64; 3], // Ks
+    pub shininess: f64, // Ns
+}
+
+impl Material {
+    // what you get for a usemtl with no matching newmtl: plain grey
+    pub fn named(name: &str) -> Self {
+        Self { name: name.to_string(), diffuse: [0.8; 3], specular: [0.0; 3], shininess: 0.0 }
+    }
+
+    pub fn color(&self) -> u32 {
+        let [r, g, b] = self.diffuse.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u32);
+        (r << 16) | (g << 8) | b
+    }
+
+    // as a specular exponent for our lighting model, 0 meaning matte
+    pub fn specular_exponent(&self) -> u32 {
+        if self.specular.iter().all(|c| *c <= 0.0) {
+            0
+        } else {
+            self.shininess.max(0.0).round() as u32
+        }
+    }
+}
+
+// one corner of a face, as indices into the mesh's arrays
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FaceVertex {
+    pub position: usize,
+    pub texcoord: Option<usize>,
+    pub normal: Option<usize>,
+}
+
+// always a triangle, n-gons are split up while loading
+#[derive(Debug, Clone, Copy)]
+pub struct Face {
+    pub vertices: [FaceVertex; 3],
+    pub material: Option<usize>, // into Mesh::materials
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Mesh {
+    pub positions: Vec<Point3D>,
+    pub normals: Vec<Vector3>,
+    pub texcoords: Vec<(f64, f64)>,
+    pub faces: Vec<Face>,
+    pub materials: Vec<Material>,
+    pub material_libs: Vec<String>, // mtllib names, relative to the .obj
+}
+
+impl Mesh {
+    // reads the .obj and any .mtl files it names next to it
+    pub fn load_obj(path: &Path) -> Result<Self, ObjError> {
+        let src = read(path)?;
+        let mut mesh = Self::parse_obj(&src).map_err(|e| e.in_file(path))?;
+
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for lib in mesh.material_libs.clone() {
+            let lib_path = dir.join(&lib);
+            let src = read(&lib_path)?;
+            let materials = parse_mtl(&src).map_err(|e| e.in_file(&lib_path))?;
+            mesh.apply_materials(materials);
+        }
+
+        Ok(mesh)
+    }
+
+    // materials named by usemtl start out as Material::named placeholders
+    pub fn parse_obj(src: &str) -> Result<Self, ObjError> {
+        let mut mesh = Mesh::default();
+        let mut current_material = None;
+
+        for (i, raw) in src.lines().enumerate() {
+            let line = i + 1;
+            let err = |message: String| ObjError::Parse { file: None, line, message };
+
+            let content = raw.split('#').next().unwrap_or("").trim();
+            let mut words = content.split_whitespace();
+            let Some(keyword) = words.next() else {
+                continue;
+            };
+            let args: Vec<&str> = words.collect();
+
+            match keyword {
+                "v" => {
+                    let [x, y, z] = numbers::<3>(&args, 3..=4).map_err(err)?;
+                    mesh.positions.push(Point3D::new(x, y, z));
+                },
+                "vn" => {
+                    let [x, y, z] = numbers::<3>(&args, 3..=3).map_err(err)?;
+                    mesh.normals.push(Vector3 { x, y, z });
+                },
+                "vt" => {
+                    let [u, v] = numbers::<2>(&args, 1..=3).map_err(err)?;
+                    mesh.texcoords.push((u, v));
+                },
+                "f" => {
+                    if args.len() < 3 {
+                        return Err(err(format!("a face needs at least 3 vertices, got {}", args.len())));
+                    }
+                    let corners = args.iter()
+                        .map(|a| mesh.face_vertex(a))
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(err)?;
+
+                    // fan out from the first corner. right for anything convex,
+                    // which is what exporters give us in practice
+                    for k in 1..corners.len() - 1 {
+                        mesh.faces.push(Face {
+                            vertices: [corners[0], corners[k], corners[k + 1]],
+                            material: current_material,
+                        });
+                    }
+                },
+                "usemtl" => {
+                    let name = args.first().ok_or_else(|| err("usemtl needs a material name".to_string()))?;
+                    let index = match mesh.materials.iter().position(|m| m.name == *name) {
+                        Some(index) => index,
+                        None => {
+                            mesh.materials.push(Material::named(name));
+                            mesh.materials.len() - 1
+                        },
+                    };
+                    current_material = Some(index);
+                },
+                "mtllib" => {
+                    if args.is_empty() {
+                        return Err(err("mtllib needs a file name".to_string()));
+                    }
+                    mesh.material_libs.push(args.join(" "));
+                },
+                // object and group names, smoothing groups, and the long tail
+                // of curve/surface statements don't matter to us
+                _ => {},
+            }
+        }
+
+        Ok(mesh)
+    }
+
+    // "v", "v/vt", "v//vn" or "v/vt/vn", 1-based, negative counts from the end
+    fn face_vertex(&self, word: &str) -> Result<FaceVertex, String> {
+        let mut parts = word.split('/');
+        let position = resolve_index(parts.next(), self.positions.len(), "vertex", word)?
+            .ok_or_else(|| format!("face vertex '{word}' has no position"))?;
+        let texcoord = resolve_index(parts.next(), self.texcoords.len(), "texture coordinate", word)?;
+        let normal = resolve_index(parts.next(), self.normals.len(), "normal", word)?;
+
+        if parts.next().is_some() {
+            return Err(format!("face vertex '{word}' has too many parts"));
+        }
+        Ok(FaceVertex { position, texcoord, normal })
+    }
+
+    // fill in placeholders with what the .mtl actually says
+    fn apply_materials(&mut self, materials: Vec<Material>) {
+        for material in materials {
+            if let Some(existing) = self.materials.iter_mut().find(|m| m.name == material.name) {
+                *existing = material;
+            }
+        }
+    }
+
+    // flat per-face normal when the file didn't give us any
+    pub fn face_normal(&self, face: &Face) -> Vector3 {
+        let [a, b, c] = face.vertices.map(|v| self.positions[v.position]);
+        (b - a).cross(c - a).normalize()
+    }
+}
+
+fn read(path: &Path) -> Result<String, ObjError> {
+    std::fs::read_to_string(path).map_err(|source| ObjError::Io { path: path.to_path_buf(), source })
+}
+
+fn numbers<const N: usize>(args: &[&str], count: std::ops::RangeInclusive<usize>) -> Result<[f64; N], String> {
+    if !count.contains(&args.len()) {
+        return Err(format!("expected {} to {} numbers, got {}", count.start(), count.end(), args.len()));
+    }
+
+    let mut out = [0.0; N];
+    for (slot, arg) in out.iter_mut().zip(args) {
+        *slot = arg.parse().map_err(|_| format!("'{arg}' is not a number"))?;
+    }
+    Ok(out)
+}
+
+fn resolve_index(part: Option<&str>, len: usize, what: &str, word: &str) -> Result<Option<usize>, String> {
+    let part = match part {
+        None | Some("") => return Ok(None),
+        Some(part) => part,
+    };
+
+    let index: i64 = part.parse().map_err(|_| format!("bad {what} index in '{word}'"))?;
+    let resolved = if index < 0 { len as i64 + index } else { index - 1 };
+
+    if index == 0 || resolved < 0 || resolved >= len as i64 {
+        return Err(format!("{what} index {index} in '{word}' is out of range (have {len})"));
+    }
+    Ok(Some(resolved as usize))
+}
+
+pub fn parse_mtl(src: &str) -> Result<Vec<Material>, ObjError> {
+    let mut materials: Vec<Material> = Vec::new();
+
+    for (i, raw) in src.lines().enumerate() {
+        let line = i + 1;
+        let err = |message: String| ObjError::Parse { file: None, line, message };
+
+        let content = raw.split('#').next().unwrap_or("").trim();
+        let mut words = content.split_whitespace();
+        let Some(keyword) = words.next() else {
+            continue;
+        };
+        let args: Vec<&str> = words.collect();
+
+        if keyword == "newmtl" {
+            let name = args.first().ok_or_else(|| err("newmtl needs a material name".to_string()))?;
+            materials.push(Material::named(name));
+            continue;
+        }
+
+        let current = match keyword {
+            "Kd" | "Ks" | "Ns" => materials.last_mut()
+                .ok_or_else(|| err(format!("{keyword} before any newmtl")))?,
+            _ => continue, // textures, transparency, illum and so on
+        };
+
+        match keyword {
+            "Kd" => current.diffuse = numbers::<3>(&args, 3..=3).map_err(err)?,
+            "Ks" => current.specular = numbers::<3>(&args, 3..=3).map_err(err)?,
+            _ => current.shininess = numbers::<1>(&args, 1..=1).map_err(err)?[0],
+        }
+    }
+
+    Ok(materials)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quads_become_two_triangles() {
+        let src = "\
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vn 0 0 -1
+vt 0 0
+usemtl red
+f 1/1/1 2/1/1 3/1/1 4/1/1
+f -4//1 -3//1 -2//1
+";
+        let mesh = Mesh::parse_obj(src).unwrap();
+
+        assert_eq!(mesh.faces.len(), 3);
+        assert_eq!(mesh.faces[1].vertices.map(|v| v.position), [0, 2, 3]);
+        assert_eq!(mesh.faces[2].vertices[0].texcoord, None);
+        assert_eq!(mesh.faces[2].vertices[0].normal, Some(0));
+        assert_eq!(mesh.faces[0].material, Some(0));
+        assert_eq!(mesh.materials[0].name, "red");
+    }
+
+    #[test]
+    fn malformed_lines_report_their_number() {
+        let src = "v 0 0 0\nv 1 0 0\n\nf 1 2 7\n";
+
+        match Mesh::parse_obj(src) {
+            Err(ObjError::Parse { line, .. }) => assert_eq!(line, 4),
+            _ => panic!("expected a parse error"),
+        }
+        assert!(Mesh::parse_obj("v 1 two 3").is_err());
+    }
+
+    #[test]
+    fn mtl_colors() {
+        let materials = parse_mtl("newmtl red\nKd 1 0 0\nKs 0.5 0.5 0.5\nNs 250\n").unwrap();
+
+        assert_eq!(materials[0].color(), 0xFF0000);
+        assert_eq!(materials[0].specular_exponent(), 250);
+        assert!(parse_mtl("Kd 1 0 0").is_err());
+    }
+}
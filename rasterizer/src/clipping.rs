@@ -0,0 +1,261 @@
+// frustum clipping the way the book does it: in camera space, before
+// projection, against the planes through the camera and the viewport edges.
+// with a pinhole camera that cuts away the same things clipping in
+// homogeneous clip space would, without a projection matrix in between
+
+use cg_common::math::{Matrix4, Point3D, Vector3};
+
+use crate::projection::Camera;
+
+// n.p + d = 0. points with a positive signed distance are inside
+#[derive(Debug, Clone, Copy)]
+pub struct Plane {
+    pub normal: Vector3,
+    pub d: f64,
+}
+
+impl Plane {
+    pub fn new(normal: Vector3, d: f64) -> Self {
+        Self { normal: normal.normalize(), d }
+    }
+
+    pub fn signed_distance(&self, p: Point3D) -> f64 {
+        self.normal.dot(p - Point3D::new(0.0, 0.0, 0.0)) + self.d
+    }
+
+    // where the segment a-b crosses the plane
+    fn intersect(&self, a: Point3D, b: Point3D) -> Point3D {
+        let ab = b - a;
+        let t = -self.signed_distance(a) / self.normal.dot(ab);
+        a + ab * t
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BoundingSphere {
+    pub center: Point3D,
+    pub radius: f64,
+}
+
+impl BoundingSphere {
+    // not the tightest sphere, but cheap: centered on the vertex average
+    pub fn from_points(points: &[Point3D]) -> Self {
+        if points.is_empty() {
+            return Self { center: Point3D::new(0.0, 0.0, 0.0), radius: 0.0 };
+        }
+
+        let n = points.len() as f64;
+        let sum = points.iter().fold(Vector3 { x: 0.0, y: 0.0, z: 0.0 }, |acc, p| {
+            acc + (*p - Point3D::new(0.0, 0.0, 0.0))
+        });
+        let center = Point3D::new(0.0, 0.0, 0.0) + sum * (1.0 / n);
+        let radius = points.iter().map(|p| (*p - center).len()).fold(0.0, f64::max);
+
+        Self { center, radius }
+    }
+
+    pub fn transform(&self, m: Matrix4) -> Self {
+        // scale the radius by the largest axis scale so it still covers everything
+        let scale = (0..3)
+            .map(|c| Vector3 { x: m.m[0][c], y: m.m[1][c], z: m.m[2][c] }.len())
+            .fold(0.0, f64::max);
+
+        Self { center: m.transform_point(self.center), radius: self.radius * scale }
+    }
+}
+
+impl Camera {
+    // near, left, right, bottom, top, in camera space. the side planes all
+    // pass through the camera and the edges of the viewport
+    pub fn clipping_planes(&self) -> [Plane; 5] {
+        let d = self.distance;
+        let hw = self.viewport_width / 2.0;
+        let hh = self.viewport_height / 2.0;
+
+        [
+            Plane::new(Vector3 { x: 0.0, y: 0.0, z: 1.0 }, -d),
+            Plane::new(Vector3 { x: d, y: 0.0, z: hw }, 0.0),
+            Plane::new(Vector3 { x: -d, y: 0.0, z: hw }, 0.0),
+            Plane::new(Vector3 { x: 0.0, y: d, z: hh }, 0.0),
+            Plane::new(Vector3 { x: 0.0, y: -d, z: hh }, 0.0),
+        ]
+    }
+}
+
+pub enum SphereClip {
+    Inside,
+    Outside,
+    Intersecting,
+}
+
+pub fn clip_sphere(sphere: &BoundingSphere, planes: &[Plane]) -> SphereClip {
+    let mut result = SphereClip::Inside;
+    for plane in planes {
+        let distance = plane.signed_distance(sphere.center);
+        if distance < -sphere.radius {
+            return SphereClip::Outside;
+        }
+        if distance < sphere.radius {
+            result = SphereClip::Intersecting;
+        }
+    }
+    result
+}
+
+// clips one triangle against one plane. winding order is preserved, so
+// back-face culling still works on the pieces
+pub fn clip_triangle(triangle: [Point3D; 3], plane: &Plane) -> Vec<[Point3D; 3]> {
+    let inside: Vec<bool> = triangle.iter().map(|v| plane.signed_distance(*v) >= 0.0).collect();
+    let count = inside.iter().filter(|i| **i).count();
+
+    // rotate so the odd one out comes first (cyclic, so winding is kept)
+    let rotated = |first: usize| [triangle[first], triangle[(first + 1) % 3], triangle[(first + 2) % 3]];
+
+    match count {
+        0 => Vec::new(),
+        3 => vec![triangle],
+        1 => {
+            let first = inside.iter().position(|i| *i).unwrap();
+            let [a, b, c] = rotated(first);
+            vec![[a, plane.intersect(a, b), plane.intersect(a, c)]]
+        },
+        _ => {
+            let first = inside.iter().position(|i| !*i).unwrap();
+            let [c, a, b] = rotated(first);
+            let b_prime = plane.intersect(b, c);
+            let a_prime = plane.intersect(a, c);
+            vec![[a, b, b_prime], [a, b_prime, a_prime]]
+        },
+    }
+}
+
+pub fn clip_triangle_against_planes(triangle: [Point3D; 3], planes: &[Plane]) -> Vec<[Point3D; 3]> {
+    let mut triangles = vec![triangle];
+    for plane in planes {
+        triangles = triangles.into_iter()
+            .flat_map(|t| clip_triangle(t, plane))
+            .collect();
+        if triangles.is_empty() {
+            break;
+        }
+    }
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // z >= 1
+    fn near() -> Plane {
+        Plane::new(Vector3 { x: 0.0, y: 0.0, z: 1.0 }, -1.0)
+    }
+
+    fn normal([a, b, c]: [Point3D; 3]) -> Vector3 {
+        (b - a).cross(c - a)
+    }
+
+    fn area(triangle: [Point3D; 3]) -> f64 {
+        normal(triangle).len() / 2.0
+    }
+
+    fn keeps_winding(original: [Point3D; 3], pieces: &[[Point3D; 3]]) -> bool {
+        let n = normal(original).normalize();
+        pieces.iter().all(|p| (normal(*p).normalize().dot(n) - 1.0).abs() < 1e-9)
+    }
+
+    #[test]
+    fn all_or_nothing() {
+        let inside = [Point3D::new(0.0, 0.0, 2.0), Point3D::new(1.0, 0.0, 2.0), Point3D::new(0.0, 1.0, 3.0)];
+        let outside = [Point3D::new(0.0, 0.0, 0.0), Point3D::new(1.0, 0.0, 0.5), Point3D::new(0.0, 1.0, -1.0)];
+
+        assert_eq!(clip_triangle(inside, &near()).len(), 1);
+        assert!(clip_triangle(outside, &near()).is_empty());
+    }
+
+    #[test]
+    fn one_inside_leaves_a_smaller_triangle() {
+        let triangle = [Point3D::new(0.0, 1.0, 0.0), Point3D::new(0.0, 0.0, 2.0), Point3D::new(1.0, 0.0, 0.0)];
+        let pieces = clip_triangle(triangle, &near());
+
+        assert_eq!(pieces.len(), 1);
+        let [a, b, c] = pieces[0];
+        // the inside vertex, then where its two edges cross z = 1
+        assert_eq!((a.x, a.y, a.z), (0.0, 0.0, 2.0));
+        assert!((b.x - 0.5).abs() < 1e-9 && b.y.abs() < 1e-9 && (b.z - 1.0).abs() < 1e-9);
+        assert!(c.x.abs() < 1e-9 && (c.y - 0.5).abs() < 1e-9 && (c.z - 1.0).abs() < 1e-9);
+        assert!(keeps_winding(triangle, &pieces));
+        // half of each edge survives, so a quarter of the area
+        assert!((area(pieces[0]) - area(triangle) / 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn two_inside_splits_in_two() {
+        // every rotation and both windings, since which vertex is the odd one
+        // out decides how the quad gets split
+        let triangle = [Point3D::new(0.0, 0.0, 2.0), Point3D::new(1.0, 0.0, 2.0), Point3D::new(0.0, 1.0, 0.0)];
+        for shift in 0..3 {
+            for flip in [false, true] {
+                let mut t = [triangle[shift], triangle[(shift + 1) % 3], triangle[(shift + 2) % 3]];
+                if flip {
+                    t.swap(0, 1);
+                }
+
+                let pieces = clip_triangle(t, &near());
+                assert_eq!(pieces.len(), 2);
+                assert!(pieces.iter().flatten().all(|v| v.z >= 1.0 - 1e-9));
+                assert!(keeps_winding(t, &pieces));
+                // the cut-off corner is a quarter of the whole
+                let kept: f64 = pieces.iter().map(|p| area(*p)).sum();
+                assert!((kept - area(t) * 0.75).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn clipped_against_the_frustum() {
+        let camera = Camera::new(Point3D::new(0.0, 0.0, 0.0), 1.0, 1.0, 1.0);
+        let planes = camera.clipping_planes();
+
+        // crosses the near plane and pokes out the left and top
+        let triangle = [Point3D::new(-5.0, 0.0, 3.0), Point3D::new(0.0, 5.0, 3.0), Point3D::new(0.5, -0.2, 0.5)];
+        let pieces = clip_triangle_against_planes(triangle, &planes);
+
+        assert!(!pieces.is_empty());
+        assert!(keeps_winding(triangle, &pieces));
+        for v in pieces.iter().flatten() {
+            assert!(planes.iter().all(|p| p.signed_distance(*v) > -1e-9));
+        }
+
+        // behind the camera there's nothing left
+        let behind = [Point3D::new(0.0, 0.0, -1.0), Point3D::new(1.0, 0.0, -2.0), Point3D::new(0.0, 1.0, -2.0)];
+        assert!(clip_triangle_against_planes(behind, &planes).is_empty());
+    }
+
+    #[test]
+    fn spheres_against_the_frustum() {
+        let planes = Camera::new(Point3D::new(0.0, 0.0, 0.0), 1.0, 1.0, 1.0).clipping_planes();
+        let sphere = |x: f64, z: f64, radius: f64| BoundingSphere { center: Point3D::new(x, 0.0, z), radius };
+
+        assert!(matches!(clip_sphere(&sphere(0.0, 10.0, 1.0), &planes), SphereClip::Inside));
+        assert!(matches!(clip_sphere(&sphere(0.0, 1.0, 0.5), &planes), SphereClip::Intersecting));
+        assert!(matches!(clip_sphere(&sphere(20.0, 10.0, 1.0), &planes), SphereClip::Outside));
+        assert!(matches!(clip_sphere(&sphere(0.0, -5.0, 1.0), &planes), SphereClip::Outside));
+    }
+
+    #[test]
+    fn bounding_sphere_covers_its_points() {
+        let points = [Point3D::new(1.0, 2.0, 3.0), Point3D::new(-1.0, 0.0, 1.0), Point3D::new(4.0, -2.0, 0.0), Point3D::new(0.0, 0.0, 0.0)];
+        let sphere = BoundingSphere::from_points(&points);
+        assert!(points.iter().all(|p| (*p - sphere.center).len() <= sphere.radius + 1e-9));
+
+        // and still does after a non-uniform scale, a turn and a move
+        let m = Matrix4::translation(Vector3 { x: 5.0, y: -1.0, z: 2.0 })
+            * Matrix4::rotation_y(0.7)
+            * Matrix4::scale(Vector3 { x: 1.0, y: 3.0, z: 0.5 });
+        let moved = sphere.transform(m);
+        assert!(points.iter().all(|p| (m.transform_point(*p) - moved.center).len() <= moved.radius + 1e-9));
+
+        assert_eq!(BoundingSphere::from_points(&[]).radius, 0.0);
+    }
+}
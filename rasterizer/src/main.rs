@@ -1,13 +1,21 @@
+pub mod clipping;
+pub mod options;
 pub mod paint;
+pub mod projection;
+pub mod scene;
 
-use cg_common::math::{Point2D, ShadedVertex2};
+use cg_common::fly::{FlyCamera, Input};
+use cg_common::math::{Matrix4, Point3D, Vector3};
 use cg_common::canvas::Canvas;
+use cg_common::framebuffer::Framebuffer;
+use cg_common::obj::Mesh;
 use winit::dpi::LogicalSize;
 
 
 use std::error::Error;
 use std::num::NonZeroU32;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 use winit::application::ApplicationHandler;
 use winit::event::WindowEvent;
 use winit::event_loop::{self, ActiveEventLoop, EventLoop};
@@ -16,11 +24,54 @@ use winit::window::{Window, WindowId};
 // comment out for wayland and change event_loop declaration in main()
 use winit::platform::x11::EventLoopBuilderExtX11;
 
-use crate::paint::{draw_filled_triangle, draw_wireframe_triangle, draw_shaded_triangle};
+use crate::options::{Options, USAGE};
+use crate::projection::Camera;
+use crate::scene::{Instance, Model, RenderStats, Scene, render_scene};
 
 struct App {
     window: Option<Rc<Window>>,
     surface: Option<softbuffer::Surface<Rc<Window>, Rc<Window>>>,
+    framebuffer: Framebuffer,
+    scene: Scene,
+    fly: FlyCamera,
+    last_update: Instant, // last time fly.update ran
+}
+
+const FRAME: Duration = Duration::from_millis(16);
+
+// the book's two cubes, or two copies of whatever --model loaded
+fn build_scene(model: Rc<Model>) -> Scene {
+    let mut scene = Scene::new();
+    scene.add(Instance::from_trs(model.clone(),
+        Vector3 { x: -1.5, y: 0.0, z: 7.0 }, Matrix4::identity(), 0.75));
+    scene.add(Instance::from_trs(model,
+        Vector3 { x: 1.25, y: 2.5, z: 7.5 }, Matrix4::rotation_y(195f64.to_radians()), 1.0));
+
+    scene
+}
+
+// where the book puts the camera, with its 1x1 viewport at distance 1
+fn start_camera() -> FlyCamera {
+    FlyCamera::new(Point3D::new(-3.0, 1.0, 2.0), 30f64.to_radians(), 0.0, 2.0 * 0.5f64.atan())
+}
+
+fn render(scene: &Scene, fly: &FlyCamera, canvas: &mut Canvas) -> RenderStats {
+    // the viewport is as tall as the fov says and as wide as the window
+    let viewport_height = 2.0 * (fly.fov / 2.0).tan();
+    let viewport_width = viewport_height * canvas.width as f64 / canvas.height.max(1) as f64;
+
+    let mut camera = Camera::new(fly.position, viewport_width, viewport_height, 1.0);
+    camera.orientation = fly.orientation();
+
+    render_scene(scene, &camera, canvas)
+}
+
+impl App {
+    fn camera_changed(&self) {
+        if let Some(window) = &self.window {
+            window.request_redraw();
+        }
+    }
 }
 
 impl ApplicationHandler for App {
@@ -40,7 +91,33 @@ impl ApplicationHandler for App {
         window.request_redraw();
     }
     
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        let now = Instant::now();
+        if self.fly.is_moving() {
+            if self.fly.update((now - self.last_update).as_secs_f64()) {
+                self.camera_changed();
+            }
+            event_loop.set_control_flow(event_loop::ControlFlow::WaitUntil(now + FRAME));
+        } else {
+            event_loop.set_control_flow(event_loop::ControlFlow::Wait);
+        }
+        self.last_update = now;
+    }
+
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
+        match self.fly.handle_window_event(&event) {
+            Input::Ignored => (),
+            Input::Handled => return,
+            Input::StartedMoving => {
+                self.last_update = Instant::now(); // don't count the time we sat still
+                return;
+            },
+            Input::ViewChanged => {
+                self.camera_changed();
+                return;
+            },
+        }
+
         match event {
             WindowEvent::CloseRequested => {
                 println!("CloseRequested event. Window closed.");
@@ -60,34 +137,13 @@ impl ApplicationHandler for App {
                         )
                         .unwrap();
 
-                    let mut buffer = surface.buffer_mut().unwrap();
-                    buffer.fill(0);
-                    
-                    { // lifetime bullshit 
-                        let mut canvas = Canvas {
-                            width: buffer.width().get(),
-                            height: buffer.height().get(),
-                            buffer: &mut buffer,
-                        };
-                        // let p0 = Point2D { x: 0.0, y: 0.0 };
-                        // canvas.put_pixel(p0, 0x6495ED);
-
-                        let p1 = Point2D {x: -200.0, y: -250.0};
-                        let p2 = Point2D {x: 200.0, y: 50.0};
-                        let p3 = Point2D {x: 20.0, y: 250.0};
-
-                        let v0 = ShadedVertex2 {x: -200.0, y: -200.0, h: 0.2};
-                        let v1 = ShadedVertex2 {x: -200.0, y: 250.0, h: 1.0};
-                        let v2 = ShadedVertex2 {x: 0.0, y: 250.0, h: 0.5};
-                        
-
-                        draw_filled_triangle(p1, p2, p3, 0x123524, &mut canvas);
-                        draw_wireframe_triangle(p1,p2,p3, 0x6495ED, &mut canvas);
-                        draw_shaded_triangle(v0,v1,v2, 0x9b111e, &mut canvas);
-                        
-
-                    }
+                    self.framebuffer.resize(width, height);
+                    self.framebuffer.clear(0);
+                    let stats = render(&self.scene, &self.fly, &mut self.framebuffer.canvas());
+                    log::debug!("{stats:?}");
 
+                    let mut buffer = surface.buffer_mut().unwrap();
+                    self.framebuffer.blit(&mut buffer, width, height);
                     buffer.present().unwrap();
                 }
             }
@@ -99,13 +155,47 @@ impl ApplicationHandler for App {
 fn main() -> Result<(), Box<dyn Error>> {
     env_logger::init();
 
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+    if options.help {
+        println!("{USAGE}");
+        return Ok(());
+    }
+
+    let model = match &options.model {
+        Some(path) => Model::from_mesh(&Mesh::load_obj(path)?),
+        None => Model::cube(),
+    };
+    let scene = build_scene(Rc::new(model));
+
+    // headless: never touches winit, so this works without a display server
+    if let Some(path) = &options.output {
+        let mut framebuffer = Framebuffer::with_depth(600, 600);
+        render(&scene, &start_camera(), &mut framebuffer.canvas());
+        framebuffer.save(path)?;
+        println!("Wrote {}", path.display());
+        return Ok(());
+    }
+
     // Wayland isn't working in WSL2 so we force x11 here. Otherwise we can 
     // let event_loop = EventLoop::event_loop::new()?;
     let event_loop = EventLoop::builder().with_x11().build()?;
 
-    event_loop.set_control_flow(event_loop::ControlFlow::Poll);
+    event_loop.set_control_flow(event_loop::ControlFlow::Wait);
 
-    let mut app = App { window: None, surface: None, };
+    let mut app = App {
+        window: None,
+        surface: None,
+        framebuffer: Framebuffer::with_depth(0, 0),
+        scene,
+        fly: start_camera(),
+        last_update: Instant::now(),
+    };
     event_loop.run_app(&mut app)?;
 
     Ok(())
@@ -0,0 +1,55 @@
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+usage: rasterizer [options]
+
+  -m, --model <file>      draw a Wavefront .obj mesh instead of the cubes
+  -o, --output <file>     render once to a .png, .bmp or .ppm and exit,
+                          without opening a window
+      --help              print this and exit";
+
+#[derive(Default)]
+pub struct Options {
+    pub model: Option<PathBuf>,
+    pub output: Option<PathBuf>, // Some means headless
+    pub help: bool,
+}
+
+impl Options {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| args.next().ok_or(format!("{name} needs a value"));
+
+            match arg.as_str() {
+                "-m" | "--model" => options.model = Some(PathBuf::from(value(&arg)?)),
+                "-o" | "--output" => options.output = Some(PathBuf::from(value(&arg)?)),
+                "--help" => options.help = true,
+                _ => return Err(format!("unknown argument '{arg}'")),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn paths_and_mistakes() {
+        let options = parse(&["--model", "models/prism.obj", "-o", "out.png"]).unwrap();
+        assert_eq!(options.model, Some(PathBuf::from("models/prism.obj")));
+        assert_eq!(options.output, Some(PathBuf::from("out.png")));
+
+        assert_eq!(parse(&["--output"]).err().unwrap(), "--output needs a value");
+        assert_eq!(parse(&["--width", "10"]).err().unwrap(), "unknown argument '--width'");
+    }
+}
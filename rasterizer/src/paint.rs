@@ -1,9 +1,69 @@
-use cg_common::math::{Point2D, ShadedVertex2, lerp, lerp_f64, apply_intensity};
-use cg_common::canvas::Canvas;
+use cg_common::math::{Point2D, ShadedVertex2, lerp, apply_intensity};
+use cg_common::canvas::{Canvas, DepthMode};
+
+// Liang-Barsky. trims the segment to the canvas so we never walk (or allocate
+// for) pixels we can't see. None if the segment misses the canvas entirely
+fn clip_line(p0: Point2D, p1: Point2D, canvas: &Canvas) -> Option<(Point2D, Point2D)> {
+    if ![p0.x, p0.y, p1.x, p1.y].iter().all(|v| v.is_finite()) {
+        return None;
+    }
+
+    let (min_x, max_x, min_y, max_y) = canvas.centered_bounds();
+    let dx = p1.x - p0.x;
+    let dy = p1.y - p0.y;
+
+    let mut t0: f64 = 0.0;
+    let mut t1: f64 = 1.0;
+    let edges = [
+        (-dx, p0.x - min_x as f64),
+        (dx, max_x as f64 - p0.x),
+        (-dy, p0.y - min_y as f64),
+        (dy, max_y as f64 - p0.y),
+    ];
+
+    for (p, q) in edges {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None; // parallel and outside
+            }
+            continue;
+        }
+        let t = q / p;
+        if p < 0.0 {
+            t0 = t0.max(t);
+        } else {
+            t1 = t1.min(t);
+        }
+        if t0 > t1 {
+            return None;
+        }
+    }
+
+    Some((
+        Point2D { x: p0.x + t0 * dx, y: p0.y + t0 * dy },
+        Point2D { x: p0.x + t1 * dx, y: p0.y + t1 * dy },
+    ))
+}
+
+// true if a triangle is worth scanning at all
+fn triangle_visible(points: [&ShadedVertex2; 3], canvas: &Canvas) -> bool {
+    if !points.iter().all(|p| p.x.is_finite() && p.y.is_finite()) {
+        return false;
+    }
+
+    let (min_x, max_x, min_y, max_y) = canvas.centered_bounds();
+    let lo_x = points.iter().map(|p| p.x).fold(f64::INFINITY, f64::min);
+    let hi_x = points.iter().map(|p| p.x).fold(f64::NEG_INFINITY, f64::max);
+    let lo_y = points.iter().map(|p| p.y).fold(f64::INFINITY, f64::min);
+    let hi_y = points.iter().map(|p| p.y).fold(f64::NEG_INFINITY, f64::max);
+
+    hi_x >= min_x as f64 && lo_x <= max_x as f64 && hi_y >= min_y as f64 && lo_y <= max_y as f64
+}
 
 pub fn draw_line(point_a: Point2D, point_b: Point2D, color: u32, canvas: &mut Canvas) {
-    let mut p0 = point_a;
-    let mut p1 = point_b;
+    let Some((mut p0, mut p1)) = clip_line(point_a, point_b, canvas) else {
+        return;
+    };
 
     if (p1.x - p0.x).abs() > (p1.y - p0.y).abs() {
         if p0.x > p1.x {
@@ -30,123 +90,82 @@ pub fn draw_wireframe_triangle(p0: Point2D, p1: Point2D, p2: Point2D, color: u32
     draw_line(p2, p0, color, canvas);
 }
 
-pub fn draw_filled_triangle(point_a: Point2D, point_b: Point2D, point_c: Point2D, color: u32, canvas: &mut Canvas) {
-    // create locally mutable variables since we are not passing &muts in
-    let mut p0 = point_a;
-    let mut p1 = point_b;
-    let mut p2 = point_c;
-
-    // sort the points
-    if p1.y < p0.y { std::mem::swap(&mut p1, &mut p0); }
-    if p2.y < p0.y { std::mem::swap(&mut p2, &mut p0); }
-    if p2.y < p1.y { std::mem::swap(&mut p2, &mut p1); }
-
-    let y0 = p0.y as i32;
-    let y1 = p1.y as i32;
-    let y2 = p2.y as i32;
-
-    let mut xs0_1 = lerp(y0.into(), p0.x, y1.into(), p1.x);
-    let xs1_2 = lerp(y1.into(), p1.x, y2.into(), p2.x);
-    let xs0_2 = lerp(y0.into(), p0.x, y2.into(), p2.x);
-
-    if let Some(_) = xs0_1.pop() { }
-
-    let xs0_1_2 = [xs0_1.as_slice(), xs1_2.as_slice()].concat();
-
-    let m = xs0_1_2.len() / 2;
-
-    let mut x_left: Vec<i32> = Vec::new();
-    let mut x_right: Vec<i32> = Vec::new();
-
-    if m < xs0_2.len() && xs0_2[m] < xs0_1_2[m] {
-        x_left.extend_from_slice(&xs0_2);
-        x_right.extend_from_slice(&xs0_1_2);
-    } else {
-        x_left = xs0_1_2;
-        x_right = xs0_2;
-    }
+pub fn draw_filled_triangle(point_a: ShadedVertex2, point_b: ShadedVertex2, point_c: ShadedVertex2, color: u32, depth: DepthMode, canvas: &mut Canvas) {
+    scan_triangle([point_a, point_b, point_c], depth, canvas, |_| color);
+}
 
-    for y in y0..=y2 {
-        let idx = (y - y0) as usize;
-        
-        if idx >= x_left.len() || idx >= x_right.len() {
-            continue; 
-        }
+pub fn draw_shaded_triangle(point_a: ShadedVertex2, point_b: ShadedVertex2, point_c: ShadedVertex2, color: u32, depth: DepthMode, canvas: &mut Canvas) {
+    scan_triangle([point_a, point_b, point_c], depth, canvas, |h| apply_intensity(color, h));
+}
 
-        let xl = x_left[idx];
-        let xr = x_right[idx];
+// the value at i on the line through (i0, d0) and (i1, d1). one entry of
+// lerp's table, without building the rest of it
+fn lerp_at(i0: f64, d0: f64, i1: f64, d1: f64, i: f64) -> f64 {
+    if i0 == i1 { d0 } else { d0 + (i - i0) * (d1 - d0) / (i1 - i0) }
+}
 
-        for x in xl..=xr {
-            canvas.put_pixel(Point2D { x: x as f64, y: y as f64 }, color);
-        }
-    }
+// (x, h, inv_z) where the edge a-b crosses scanline y
+fn edge_at(a: &ShadedVertex2, b: &ShadedVertex2, y: f64) -> (f64, f64, f64) {
+    let (ya, yb) = (a.y.trunc(), b.y.trunc());
+    (lerp_at(ya, a.x, yb, b.x, y), lerp_at(ya, a.h, yb, b.h, y), lerp_at(ya, a.inv_z, yb, b.inv_z, y))
 }
 
-pub fn draw_shaded_triangle(point_a: ShadedVertex2, point_b: ShadedVertex2, point_c: ShadedVertex2, color: u32, canvas: &mut Canvas) {
-    let mut p0 = point_a;
-    let mut p1 = point_b;
-    let mut p2 = point_c;
+// the book's scanline fill, but edges and spans are worked out per row and
+// per pixel, and only for rows and pixels on the canvas. a vertex miles off
+// screen costs nothing extra instead of a table with a billion entries in it
+fn scan_triangle(points: [ShadedVertex2; 3], depth: DepthMode, canvas: &mut Canvas, shade: impl Fn(f64) -> u32) {
+    let [mut p0, mut p1, mut p2] = points;
 
+    // sort the points
     if p1.y < p0.y { std::mem::swap(&mut p1, &mut p0); }
     if p2.y < p0.y { std::mem::swap(&mut p2, &mut p0); }
     if p2.y < p1.y { std::mem::swap(&mut p2, &mut p1); }
 
-    let y0 = p0.y as i32;
-    let y1 = p1.y as i32;
-    let y2 = p2.y as i32;
-
-    let mut xs0_1 = lerp(y0.into(), p0.x, y1.into(), p1.x);
-    let mut hs0_1 = lerp_f64(y0.into(), p0.h,y1.into(), p1.h);
-    
-    let xs1_2 = lerp(y1.into(), p1.x, y2.into(), p2.x);
-    let hs1_2 = lerp_f64(y1.into(), p1.h, y2.into(), p2.h);
-
-    let xs0_2 = lerp(y0.into(), p0.x, y2.into(), p2.x);
-    let hs0_2 = lerp_f64(y0.into(), p0.h, y2.into(), p2.h);
-
-    if let Some(_) = xs0_1.pop() { }
-    let xs0_1_2 = [xs0_1.as_slice(), xs1_2.as_slice()].concat();
-
-    if let Some(_) = hs0_1.pop() { }
-    let hs0_1_2 = [hs0_1.as_slice(), hs1_2.as_slice()].concat();
-
-    let m = xs0_1_2.len() / 2;
-
-    let mut x_left: Vec<i32> = Vec::new();
-    let mut x_right: Vec<i32> = Vec::new();
-
-    let mut h_left: Vec<f64> = Vec::new();
-    let mut h_right: Vec<f64> = Vec::new();
-
-    if m < xs0_2.len() && xs0_2[m] < xs0_1_2[m] {
-        x_left.extend_from_slice(&xs0_2);
-        h_left.extend_from_slice(&hs0_2);
-        
-        x_right.extend_from_slice(&xs0_1_2);
-        h_right.extend_from_slice(&hs0_1_2);
-    } else {
-        x_left = xs0_1_2;
-        h_left = hs0_1_2;
-
-        x_right = xs0_2;
-        h_right = hs0_2;
+    if !triangle_visible([&p0, &p1, &p2], canvas) {
+        return;
     }
-
-    for y in y0..=y2 {
-        let idx = (y - y0) as usize;
-        
-        if idx >= x_left.len() || idx >= x_right.len() {
-            continue; 
+    let (min_x, max_x, min_y, max_y) = canvas.centered_bounds();
+
+    // does the long edge p0-p2 run left or right of p1
+    let long_is_left = edge_at(&p0, &p2, p1.y.trunc()).0 < p1.x;
+
+    // `as` saturates, so these are fine however far out the vertices are
+    for y in (p0.y as i32).max(min_y)..=(p2.y as i32).min(max_y) {
+        let long = edge_at(&p0, &p2, y as f64);
+        let short = if (y as f64) < p1.y.trunc() { edge_at(&p0, &p1, y as f64) } else { edge_at(&p1, &p2, y as f64) };
+        let (left, right) = if long_is_left { (long, short) } else { (short, long) };
+
+        let (xl, xr) = (left.0 as i32, right.0 as i32);
+        for x in xl.max(min_x)..=xr.min(max_x) {
+            let h = lerp_at(xl as f64, left.1, xr as f64, right.1, x as f64);
+            let inv_z = lerp_at(xl as f64, left.2, xr as f64, right.2, x as f64);
+            canvas.put_pixel_depth(Point2D { x: x as f64, y: y as f64 }, inv_z, shade(h), depth);
         }
+    }
+}
 
-        let xl = x_left[idx];
-        let xr = x_right[idx];
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let h_segment = lerp_f64(xl.into(), h_left[idx].into(), xr.into(), h_right[idx].into());
+    fn vertex(x: f64, y: f64) -> ShadedVertex2 {
+        ShadedVertex2 { x, y, h: 1.0, inv_z: 1.0 }
+    }
 
-        for x in xl..=xr {
-            let shaded_color = apply_intensity(color, h_segment[(x - xl) as usize].into());
-            canvas.put_pixel(Point2D { x: x as f64, y: y as f64 }, shaded_color);
-        }
+    #[test]
+    fn far_away_vertices_only_fill_the_canvas() {
+        let mut buffer = vec![0; 8 * 8];
+        let mut depth = vec![0.0; 8 * 8];
+        let mut canvas = Canvas { buffer: &mut buffer, depth: Some(&mut depth), width: 8, height: 8 };
+
+        // used to build edge tables a billion entries long
+        draw_filled_triangle(vertex(-1e9, -1e9), vertex(1e9, -1e9), vertex(0.0, 1e9), 0xFFFFFF, DepthMode::NONE, &mut canvas);
+        assert!(canvas.buffer.iter().all(|c| *c == 0xFFFFFF));
+
+        // partly on screen, one vertex way off to the side
+        canvas.buffer.fill(0);
+        draw_shaded_triangle(vertex(-4.0, -4.0), vertex(-4.0, 3.0), vertex(1e9, 0.0), 0xFFFFFF, DepthMode::TEST_AND_WRITE, &mut canvas);
+        assert_eq!(canvas.get_pixel(Point2D { x: 3.0, y: 0.0 }), Some(0xFFFFFF));
+        assert_eq!(canvas.get_pixel(Point2D { x: -4.0, y: 0.0 }), Some(0xFFFFFF));
     }
 }
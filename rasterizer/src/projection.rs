@@ -1,7 +1,98 @@
-//todo
+use cg_common::math::{Matrix4, Point3D, ShadedVertex2, Vector3};
 
-use cg_common::math::{Point3D, ShadedVertex2}; 
+// same model as the raytracer: a viewport of viewport_width x viewport_height
+// sitting `distance` in front of the camera, looking down +z
+pub struct Camera {
+    pub position: Point3D,
+    pub orientation: Matrix4, // camera -> world rotation
+    pub viewport_width: f64,
+    pub viewport_height: f64,
+    pub distance: f64,
+}
+
+impl Camera {
+    pub fn new(position: Point3D, viewport_width: f64, viewport_height: f64, distance: f64) -> Self {
+        Self {
+            position,
+            orientation: Matrix4::identity(),
+            viewport_width,
+            viewport_height,
+            distance,
+        }
+    }
+
+    // rotate the camera in place so it faces `target`
+    pub fn point_at(&mut self, target: Point3D, up: Vector3) {
+        self.orientation = Matrix4::look_rotation(self.position, target, up);
+    }
+
+    // rotations are orthonormal, so the inverse is just the transpose
+    pub fn world_to_camera(&self) -> Matrix4 {
+        let to_origin = Point3D::new(0.0, 0.0, 0.0) - self.position;
+        self.orientation.transpose() * Matrix4::translation(to_origin)
+    }
+
+    // v is in camera space. h is left at full intensity for the caller to shade
+    pub fn project_vertex(&self, v: Point3D, canvas_w: u32, canvas_h: u32) -> ShadedVertex2 {
+        let p = v.project_canvas(self.viewport_width, self.viewport_height, canvas_w, canvas_h, self.distance);
+
+        ShadedVertex2 { x: p.x, y: p.y, h: 1.0, inv_z: 1.0 / v.z }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn straight_ahead_is_the_center() {
+        let camera = Camera::new(Point3D::new(0.0, 0.0, 0.0), 1.0, 1.0, 1.0);
+        let v = camera.world_to_camera().transform_point(Point3D::new(0.0, 0.0, 7.0));
+        let p = camera.project_vertex(v, 200, 100);
+
+        assert!(close(p.x, 0.0) && close(p.y, 0.0));
+        assert!(close(p.inv_z, 1.0 / 7.0));
+    }
+
+    #[test]
+    fn viewport_edge_is_the_canvas_edge() {
+        // canvas coordinates are centered, so the edges are +-half the size
+        let camera = Camera::new(Point3D::new(0.0, 0.0, 0.0), 2.0, 1.5, 1.5);
+
+        let p = camera.project_vertex(Point3D::new(1.0, 0.0, 1.5), 200, 100);
+        assert!(close(p.x, 100.0) && close(p.y, 0.0));
+        let p = camera.project_vertex(Point3D::new(0.0, -0.75, 1.5), 200, 100);
+        assert!(close(p.x, 0.0) && close(p.y, -50.0));
+
+        // twice as far and twice as wide lands in the same place
+        let p = camera.project_vertex(Point3D::new(-2.0, 0.0, 3.0), 200, 100);
+        assert!(close(p.x, -100.0));
+    }
+
+    #[test]
+    fn moved_and_turned_camera() {
+        let mut camera = Camera::new(Point3D::new(1.0, 2.0, -3.0), 1.0, 1.0, 1.0);
+        camera.orientation = Matrix4::rotation_y(0.6) * Matrix4::rotation_x(-0.2);
+
+        // somewhere in front of the camera, placed in the world by hand:
+        // turned by the orientation, then moved to the camera
+        let seen = Point3D::new(0.3, -0.2, 4.0);
+        let world = camera.position + camera.orientation.transform_vector(seen - Point3D::new(0.0, 0.0, 0.0));
+
+        let v = camera.world_to_camera().transform_point(world);
+        assert!(close(v.x, seen.x) && close(v.y, seen.y) && close(v.z, seen.z));
+
+        let p = camera.project_vertex(v, 100, 100);
+        assert!(close(p.x, 0.3 / 4.0 * 100.0) && close(p.y, -0.2 / 4.0 * 100.0));
 
-pub fn project_vertex(v: Point3D) -> ShadedVertex2 {
-    
+        // and pointing it at something puts that in the middle
+        let target = Point3D::new(-4.0, 0.0, 6.0);
+        camera.point_at(target, Vector3 { x: 0.0, y: 1.0, z: 0.0 });
+        let p = camera.project_vertex(camera.world_to_camera().transform_point(target), 100, 100);
+        assert!(close(p.x, 0.0) && close(p.y, 0.0));
+    }
 }
@@ -0,0 +1,306 @@
+use std::rc::Rc;
+
+use cg_common::canvas::{Canvas, DepthMode};
+use cg_common::math::{Matrix4, Point3D, Vector3};
+use cg_common::obj::Mesh;
+
+use crate::clipping::{BoundingSphere, Plane, SphereClip, clip_sphere, clip_triangle_against_planes};
+use crate::paint::draw_filled_triangle;
+use crate::projection::Camera;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Triangle {
+    pub indices: [usize; 3], // into Model::vertices
+    pub color: u32,
+}
+
+impl Triangle {
+    pub fn new(i0: usize, i1: usize, i2: usize, color: u32) -> Self {
+        Self { indices: [i0, i1, i2], color }
+    }
+}
+
+// the order a triangle's vertices go around when you look at its front
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Winding {
+    Clockwise, // the book's convention
+    CounterClockwise,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CullMode {
+    None,
+    Back,
+    Front,
+}
+
+impl CullMode {
+    // v0, v1, v2 are in camera space, so the camera sits at the origin
+    pub fn culls(&self, winding: Winding, [v0, v1, v2]: [Point3D; 3]) -> bool {
+        if *self == CullMode::None {
+            return false;
+        }
+
+        let mut normal = (v1 - v0).cross(v2 - v0);
+        if winding == Winding::CounterClockwise {
+            normal = -normal;
+        }
+        let front_facing = normal.dot(Point3D::new(0.0, 0.0, 0.0) - v0) > 0.0;
+
+        match self {
+            CullMode::Back => !front_facing,
+            CullMode::Front => front_facing,
+            CullMode::None => false,
+        }
+    }
+}
+
+pub struct Model {
+    pub vertices: Vec<Point3D>, // model space
+    pub triangles: Vec<Triangle>,
+    pub bounding_sphere: BoundingSphere,
+    pub winding: Winding,
+    pub cull_mode: CullMode,
+}
+
+impl Model {
+    pub fn new(vertices: Vec<Point3D>, triangles: Vec<Triangle>) -> Self {
+        let bounding_sphere = BoundingSphere::from_points(&vertices);
+        Self { vertices, triangles, bounding_sphere, winding: Winding::Clockwise, cull_mode: CullMode::Back }
+    }
+
+    // the book's unit cube, centered on the origin
+    pub fn cube() -> Self {
+        let vertices = vec![
+            Point3D::new(1.0, 1.0, 1.0),
+            Point3D::new(-1.0, 1.0, 1.0),
+            Point3D::new(-1.0, -1.0, 1.0),
+            Point3D::new(1.0, -1.0, 1.0),
+            Point3D::new(1.0, 1.0, -1.0),
+            Point3D::new(-1.0, 1.0, -1.0),
+            Point3D::new(-1.0, -1.0, -1.0),
+            Point3D::new(1.0, -1.0, -1.0),
+        ];
+
+        let triangles = vec![
+            Triangle::new(0, 1, 2, 0xFF0000),
+            Triangle::new(0, 2, 3, 0xFF0000),
+            Triangle::new(4, 0, 3, 0x00FF00),
+            Triangle::new(4, 3, 7, 0x00FF00),
+            Triangle::new(5, 4, 7, 0x0000FF),
+            Triangle::new(5, 7, 6, 0x0000FF),
+            Triangle::new(1, 5, 6, 0xFFFF00),
+            Triangle::new(1, 6, 2, 0xFFFF00),
+            Triangle::new(4, 5, 1, 0xFF00FF),
+            Triangle::new(4, 1, 0, 0xFF00FF),
+            Triangle::new(2, 6, 7, 0x00FFFF),
+            Triangle::new(2, 7, 3, 0x00FFFF),
+        ];
+
+        Self::new(vertices, triangles)
+    }
+
+    // colors come from the .mtl, grey where there wasn't one. OBJ's
+    // counter-clockwise faces are clockwise for us (see cg_common::obj)
+    pub fn from_mesh(mesh: &Mesh) -> Self {
+        let triangles = mesh.faces.iter()
+            .map(|face| {
+                let color = face.material.map(|i| mesh.materials[i].color()).unwrap_or(0xCCCCCC);
+                let [a, b, c] = face.vertices.map(|v| v.position);
+                Triangle::new(a, b, c, color)
+            })
+            .collect();
+
+        Self::new(mesh.positions.clone(), triangles)
+    }
+}
+
+// a model placed in the world. models are shared so many instances can
+// point at the same vertex data
+pub struct Instance {
+    pub model: Rc<Model>,
+    pub transform: Matrix4, // model -> world
+    pub depth_mode: DepthMode, // e.g. DepthMode::NONE to draw as an overlay
+}
+
+impl Instance {
+    pub fn new(model: Rc<Model>, transform: Matrix4) -> Self {
+        Self { model, transform, depth_mode: DepthMode::default() }
+    }
+
+    // scale first, then rotate, then move into place
+    pub fn from_trs(model: Rc<Model>, position: Vector3, rotation: Matrix4, scale: f64) -> Self {
+        let transform = Matrix4::translation(position) * rotation * Matrix4::uniform_scale(scale);
+        Self::new(model, transform)
+    }
+}
+
+pub struct Scene {
+    pub instances: Vec<Instance>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self { instances: Vec::new() }
+    }
+
+    pub fn add(&mut self, instance: Instance) {
+        self.instances.push(instance);
+    }
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// what happened to the geometry during the last frame
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderStats {
+    pub instances_drawn: usize,
+    pub instances_culled: usize,
+    pub triangles_drawn: usize,
+    pub triangles_backfacing: usize, // thrown away by the model's CullMode
+    pub triangles_culled: usize, // entirely outside the frustum
+    pub triangles_clipped: usize, // split or trimmed by a frustum plane
+}
+
+pub fn render_scene(scene: &Scene, camera: &Camera, canvas: &mut Canvas) -> RenderStats {
+    let world_to_camera = camera.world_to_camera();
+    let planes = camera.clipping_planes();
+    let mut stats = RenderStats::default();
+
+    for instance in &scene.instances {
+        render_instance(instance, world_to_camera * instance.transform, camera, &planes, canvas, &mut stats);
+    }
+
+    stats
+}
+
+fn render_instance(instance: &Instance, transform: Matrix4, camera: &Camera, planes: &[Plane], canvas: &mut Canvas, stats: &mut RenderStats) {
+    let model = &instance.model;
+
+    // throw away (or accept) the whole instance up front where we can
+    let sphere = model.bounding_sphere.transform(transform);
+    let needs_clipping = match clip_sphere(&sphere, planes) {
+        SphereClip::Outside => {
+            stats.instances_culled += 1;
+            return;
+        },
+        SphereClip::Inside => false,
+        SphereClip::Intersecting => true,
+    };
+    stats.instances_drawn += 1;
+
+    // everything in camera space from here on
+    let transformed: Vec<Point3D> = model.vertices.iter()
+        .map(|v| transform.transform_point(*v))
+        .collect();
+
+    for triangle in &model.triangles {
+        let [i0, i1, i2] = triangle.indices;
+        let vertices = [transformed[i0], transformed[i1], transformed[i2]];
+
+        if model.cull_mode.culls(model.winding, vertices) {
+            stats.triangles_backfacing += 1;
+            continue;
+        }
+
+        let fully_inside = !needs_clipping || planes.iter()
+            .all(|plane| vertices.iter().all(|v| plane.signed_distance(*v) >= 0.0));
+
+        let clipped = if fully_inside {
+            vec![vertices]
+        } else {
+            let clipped = clip_triangle_against_planes(vertices, planes);
+            if clipped.is_empty() {
+                stats.triangles_culled += 1;
+                continue;
+            }
+            stats.triangles_clipped += 1;
+            clipped
+        };
+
+        for [v0, v1, v2] in clipped {
+            let p0 = camera.project_vertex(v0, canvas.width, canvas.height);
+            let p1 = camera.project_vertex(v1, canvas.width, canvas.height);
+            let p2 = camera.project_vertex(v2, canvas.width, canvas.height);
+
+            draw_filled_triangle(p0, p1, p2, triangle.color, instance.depth_mode, canvas);
+            stats.triangles_drawn += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the cube straight ahead, so only its -z face (blue) faces the camera.
+    // its +z face (red) is directly behind, and we'd only see the insides of
+    // the four sides
+    fn render_cube(cull_mode: CullMode, winding: Winding) -> (u32, RenderStats) {
+        let mut model = Model::cube();
+        model.cull_mode = cull_mode;
+        model.winding = winding;
+        render_model(model)
+    }
+
+    // 5 in front of a camera at the origin, and what ends up in the middle of the canvas
+    fn render_model(model: Model) -> (u32, RenderStats) {
+        let mut scene = Scene::new();
+        scene.add(Instance::new(Rc::new(model), Matrix4::translation(Vector3 { x: 0.0, y: 0.0, z: 5.0 })));
+        let camera = Camera::new(Point3D::new(0.0, 0.0, 0.0), 1.0, 1.0, 1.0);
+
+        let (width, height) = (16, 16);
+        let mut buffer = vec![0; width * height];
+        let mut depth = vec![0.0; width * height];
+        let mut canvas = Canvas { buffer: &mut buffer, depth: Some(&mut depth), width: width as u32, height: height as u32 };
+        let stats = render_scene(&scene, &camera, &mut canvas);
+
+        (canvas.get_pixel_screen(8, 8).unwrap(), stats)
+    }
+
+    #[test]
+    fn cull_modes_and_windings() {
+        let (blue, red) = (0x0000FF, 0xFF0000);
+        let cases = [
+            (CullMode::None, Winding::Clockwise, blue, 0),
+            (CullMode::Back, Winding::Clockwise, blue, 10), // everything but the front face
+            (CullMode::Front, Winding::Clockwise, red, 2), // just the front face, showing the back one
+            (CullMode::None, Winding::CounterClockwise, blue, 0),
+            // flipped, blue is the only back face and the rest face the camera
+            (CullMode::Back, Winding::CounterClockwise, red, 2),
+            (CullMode::Front, Winding::CounterClockwise, blue, 10),
+        ];
+
+        for (cull_mode, winding, center, culled) in cases {
+            let (pixel, stats) = render_cube(cull_mode, winding);
+            assert_eq!(pixel, center, "{cull_mode:?} {winding:?}");
+            assert_eq!(stats.triangles_backfacing, culled, "{cull_mode:?} {winding:?}");
+            assert_eq!(stats.triangles_drawn, 12 - culled);
+        }
+    }
+
+    #[test]
+    fn obj_faces_keep_their_front() {
+        // (b - a) x (c - a) of "f 1 2 3" points at the camera, so it's OBJ's front face
+        let obj = "\
+v -1 -1 0
+v -1 2 0
+v 2 -1 0
+f 1 2 3
+";
+        let facing = Model::from_mesh(&Mesh::parse_obj(obj).unwrap());
+        assert_eq!((facing.winding, facing.cull_mode), (Winding::Clockwise, CullMode::Back));
+        let (pixel, stats) = render_model(facing);
+        assert_eq!(pixel, 0xCCCCCC);
+        assert_eq!((stats.triangles_drawn, stats.triangles_backfacing), (1, 0));
+
+        let reversed = Model::from_mesh(&Mesh::parse_obj(&obj.replace("f 1 2 3", "f 1 3 2")).unwrap());
+        let (pixel, stats) = render_model(reversed);
+        assert_eq!(pixel, 0);
+        assert_eq!((stats.triangles_drawn, stats.triangles_backfacing), (0, 1));
+    }
+}
@@ -0,0 +1,432 @@
+// bounding volume hierarchy. knows nothing about primitives, just boxes and
+// indices into whatever list the caller built it from, so the scene and
+// meshes can both use it
+
+use cg_common::math::{Point3D, Ray, Vector3};
+
+use crate::primitive::Hit;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    pub min: Point3D,
+    pub max: Point3D,
+}
+
+impl Aabb {
+    // any two opposite corners
+    pub fn new(a: Point3D, b: Point3D) -> Self {
+        Self {
+            min: Point3D::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+            max: Point3D::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)),
+        }
+    }
+
+    // contains nothing, and union with it is a no-op
+    pub fn empty() -> Self {
+        Self {
+            min: Point3D::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Point3D::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+
+    pub fn around(center: Point3D, half_extent: Vector3) -> Self {
+        Self::new(center + -half_extent, center + half_extent)
+    }
+
+    pub fn from_points(points: &[Point3D]) -> Self {
+        points.iter().fold(Self::empty(), |b, p| b.union(Self { min: *p, max: *p }))
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            min: Point3D::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y), self.min.z.min(other.min.z)),
+            max: Point3D::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y), self.max.z.max(other.max.z)),
+        }
+    }
+
+    pub fn centroid(&self) -> Point3D {
+        Point3D::new(
+            (self.min.x + self.max.x) * 0.5,
+            (self.min.y + self.max.y) * 0.5,
+            (self.min.z + self.max.z) * 0.5,
+        )
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let d = self.max - self.min;
+        if d.x < 0.0 || d.y < 0.0 || d.z < 0.0 {
+            return 0.0; // empty
+        }
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    // slab test, returns where the ray enters (or t_min if it starts inside).
+    // takes 1 / direction since traversal tests the same ray against lots of boxes
+    pub fn hit(&self, origin: Point3D, inv_dir: Vector3, t_min: f64, t_max: f64) -> Option<f64> {
+        let mut near = t_min;
+        let mut far = t_max;
+
+        for axis in 0..3 {
+            let (lo, hi, o) = (component(self.min, axis), component(self.max, axis), component(origin, axis));
+            let inv = vcomponent(inv_dir, axis);
+
+            // parallel to this slab: either always inside it or never. doing
+            // the math would give 0 * inf = NaN for rays starting on a face
+            if inv.is_infinite() {
+                if o < lo || o > hi {
+                    return None;
+                }
+                continue;
+            }
+
+            let t0 = (lo - o) * inv;
+            let t1 = (hi - o) * inv;
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+            if near > far {
+                return None;
+            }
+        }
+
+        Some(near)
+    }
+}
+
+fn component(p: Point3D, axis: usize) -> f64 {
+    match axis {
+        0 => p.x,
+        1 => p.y,
+        _ => p.z,
+    }
+}
+
+fn vcomponent(v: Vector3, axis: usize) -> f64 {
+    match axis {
+        0 => v.x,
+        1 => v.y,
+        _ => v.z,
+    }
+}
+
+// interior nodes keep their children next to each other at `first` and
+// `first + 1`, leaves own indices[first..first + count]
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    bounds: Aabb,
+    first: usize,
+    count: usize, // 0 for interior nodes
+}
+
+pub struct Bvh {
+    nodes: Vec<Node>,
+    indices: Vec<usize>,
+}
+
+const BINS: usize = 12;
+const TRAVERSAL_COST: f64 = 1.0; // relative to one intersection test
+
+impl Bvh {
+    // `bounds[i]` is the box around item i, and i is what queries hand back
+    pub fn build(bounds: &[Aabb]) -> Self {
+        let mut bvh = Self { nodes: Vec::new(), indices: (0..bounds.len()).collect() };
+        if bounds.is_empty() {
+            return bvh;
+        }
+
+        let centroids: Vec<Point3D> = bounds.iter().map(Aabb::centroid).collect();
+        bvh.nodes.push(Node { bounds: Aabb::empty(), first: 0, count: bounds.len() });
+        bvh.subdivide(0, bounds, &centroids);
+        bvh
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.nodes.first().map(|n| n.bounds).unwrap_or_else(Aabb::empty)
+    }
+
+    fn subdivide(&mut self, node: usize, bounds: &[Aabb], centroids: &[Point3D]) {
+        let Node { first, count, .. } = self.nodes[node];
+        let items = &self.indices[first..first + count];
+        let node_bounds = items.iter().fold(Aabb::empty(), |b, &i| b.union(bounds[i]));
+        self.nodes[node].bounds = node_bounds;
+
+        if count <= 1 {
+            return;
+        }
+
+        let Some((axis, split, cost)) = best_split(items, bounds, centroids) else {
+            return; // every centroid in the same spot, nothing to split on
+        };
+        // surface area heuristic: only split if the children are expected to
+        // be cheaper than testing everything in this node
+        let leaf_cost = count as f64;
+        if cost / node_bounds.surface_area().max(f64::MIN_POSITIVE) + TRAVERSAL_COST >= leaf_cost {
+            return;
+        }
+
+        // partition in place around the split plane
+        let items = &mut self.indices[first..first + count];
+        let mut left = 0;
+        for i in 0..items.len() {
+            if component(centroids[items[i]], axis) < split {
+                items.swap(i, left);
+                left += 1;
+            }
+        }
+        if left == 0 || left == count {
+            return;
+        }
+
+        let children = self.nodes.len();
+        self.nodes.push(Node { bounds: Aabb::empty(), first, count: left });
+        self.nodes.push(Node { bounds: Aabb::empty(), first: first + left, count: count - left });
+        self.nodes[node].first = children;
+        self.nodes[node].count = 0;
+
+        self.subdivide(children, bounds, centroids);
+        self.subdivide(children + 1, bounds, centroids);
+    }
+
+    // nearest hit, if any. `intersect(item, t_min, t_max)` tests one item;
+    // t_max shrinks as closer hits turn up
+    pub fn closest(&self, ray: &Ray, t_min: f64, t_max: f64, mut intersect: impl FnMut(usize, f64, f64) -> Option<Hit>) -> Option<(usize, Hit)> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let inv_dir = Vector3 { x: 1.0 / ray.direction.x, y: 1.0 / ray.direction.y, z: 1.0 / ray.direction.z };
+        let mut closest: Option<(usize, Hit)> = None;
+        let mut t_max = t_max;
+
+        // (node, where the ray enters its box)
+        let entry = self.nodes[0].bounds.hit(ray.origin, inv_dir, t_min, t_max)?;
+        let mut stack = vec![(0, entry)];
+
+        while let Some((node, entry)) = stack.pop() {
+            if entry > t_max {
+                continue; // something closer turned up since this was pushed
+            }
+            let Node { first, count, .. } = self.nodes[node];
+
+            if count > 0 {
+                for &item in &self.indices[first..first + count] {
+                    if let Some(hit) = intersect(item, t_min, t_max) {
+                        t_max = hit.t;
+                        closest = Some((item, hit));
+                    }
+                }
+                continue;
+            }
+
+            // push the farther child first so the nearer one gets visited
+            // first and shrinks t_max for the other
+            let a = self.nodes[first].bounds.hit(ray.origin, inv_dir, t_min, t_max).map(|t| (first, t));
+            let b = self.nodes[first + 1].bounds.hit(ray.origin, inv_dir, t_min, t_max).map(|t| (first + 1, t));
+            match (a, b) {
+                (Some(a), Some(b)) if a.1 <= b.1 => stack.extend([b, a]),
+                (Some(a), Some(b)) => stack.extend([a, b]),
+                (a, b) => stack.extend(a.or(b)),
+            }
+        }
+
+        closest
+    }
+
+    // stops at the first item `hits(item, t_min, t_max)` says yes to, in no
+    // particular order
+    pub fn any(&self, ray: &Ray, t_min: f64, t_max: f64, mut hits: impl FnMut(usize, f64, f64) -> bool) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let inv_dir = Vector3 { x: 1.0 / ray.direction.x, y: 1.0 / ray.direction.y, z: 1.0 / ray.direction.z };
+        let mut stack = vec![0];
+
+        while let Some(node) = stack.pop() {
+            let Node { bounds, first, count } = self.nodes[node];
+            if bounds.hit(ray.origin, inv_dir, t_min, t_max).is_none() {
+                continue;
+            }
+
+            if count > 0 {
+                if self.indices[first..first + count].iter().any(|&item| hits(item, t_min, t_max)) {
+                    return true;
+                }
+            } else {
+                stack.extend([first, first + 1]);
+            }
+        }
+
+        false
+    }
+}
+
+// binned SAH along each axis. gives the axis, the split position and the
+// (unnormalized) cost of splitting there
+fn best_split(items: &[usize], bounds: &[Aabb], centroids: &[Point3D]) -> Option<(usize, f64, f64)> {
+    let mut best: Option<(usize, f64, f64)> = None;
+
+    for axis in 0..3 {
+        let (lo, hi) = items.iter()
+            .map(|&i| component(centroids[i], axis))
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), c| (lo.min(c), hi.max(c)));
+        if hi - lo <= 0.0 {
+            continue;
+        }
+
+        let scale = BINS as f64 / (hi - lo);
+        let bin_of = |c: f64| (((c - lo) * scale) as usize).min(BINS - 1);
+
+        let mut bin_bounds = [Aabb::empty(); BINS];
+        let mut bin_counts = [0usize; BINS];
+        for &i in items {
+            let bin = bin_of(component(centroids[i], axis));
+            bin_bounds[bin] = bin_bounds[bin].union(bounds[i]);
+            bin_counts[bin] += 1;
+        }
+
+        // sweep from both ends so each of the BINS - 1 planes is O(1)
+        let mut left_area = [0.0; BINS - 1];
+        let mut left_count = [0; BINS - 1];
+        let (mut acc_bounds, mut acc_count) = (Aabb::empty(), 0);
+        for i in 0..BINS - 1 {
+            acc_bounds = acc_bounds.union(bin_bounds[i]);
+            acc_count += bin_counts[i];
+            left_area[i] = acc_bounds.surface_area();
+            left_count[i] = acc_count;
+        }
+
+        let (mut acc_bounds, mut acc_count) = (Aabb::empty(), 0);
+        for i in (1..BINS).rev() {
+            acc_bounds = acc_bounds.union(bin_bounds[i]);
+            acc_count += bin_counts[i];
+
+            let plane = i - 1;
+            let cost = left_area[plane] * left_count[plane] as f64 + acc_bounds.surface_area() * acc_count as f64;
+            if left_count[plane] > 0 && acc_count > 0 && best.is_none_or(|(_, _, c)| cost < c) {
+                best = Some((axis, lo + i as f64 / scale, cost));
+            }
+        }
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cg_common::color::Color;
+    use crate::primitive::{Primitive, Sphere, Triangle};
+
+    fn spheres() -> Vec<Sphere> {
+        // a 10x10x10 grid of small spheres
+        (0..1000).map(|i| {
+            let center = Point3D::new((i % 10) as f64, (i / 10 % 10) as f64, (i / 100) as f64 + 5.0);
+            Sphere::new(center, 0.3, Color::WHITE, 0, 0.0)
+        }).collect()
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let spheres = spheres();
+        let bounds: Vec<Aabb> = spheres.iter().map(|s| s.bounding_box().unwrap()).collect();
+        let bvh = Bvh::build(&bounds);
+
+        for i in 0..500 {
+            // rays fanning out from behind the grid
+            let a = i as f64 * 0.37;
+            let direction = Vector3 { x: a.sin() * 0.6, y: (a * 1.7).cos() * 0.6, z: 1.0 };
+            let ray = Ray { origin: Point3D::new(4.5, 4.5, 0.0), direction };
+
+            let brute = spheres.iter().enumerate()
+                .filter_map(|(i, s)| s.intersect(&ray, 0.001, 100.0).map(|h| (i, h.t)))
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            let fast = bvh.closest(&ray, 0.001, 100.0, |i, t_min, t_max| spheres[i].intersect(&ray, t_min, t_max))
+                .map(|(i, h)| (i, h.t));
+
+            assert_eq!(brute, fast);
+        }
+    }
+
+    #[test]
+    fn any_agrees_with_closest() {
+        let spheres = spheres();
+        let bounds: Vec<Aabb> = spheres.iter().map(|s| s.bounding_box().unwrap()).collect();
+        let bvh = Bvh::build(&bounds);
+
+        for i in 0..500 {
+            let a = i as f64 * 0.37;
+            let direction = Vector3 { x: a.sin() * 0.6, y: (a * 1.7).cos() * 0.6, z: 1.0 };
+            let ray = Ray { origin: Point3D::new(4.5, 4.5, 0.0), direction };
+
+            let closest = bvh.closest(&ray, 0.001, 8.0, |i, t_min, t_max| spheres[i].intersect(&ray, t_min, t_max));
+            let any = bvh.any(&ray, 0.001, 8.0, |i, t_min, t_max| spheres[i].occludes(&ray, t_min, t_max));
+            assert_eq!(closest.is_some(), any);
+        }
+    }
+
+    #[test]
+    fn empty_hierarchy_misses() {
+        let bvh = Bvh::build(&[]);
+        let ray = Ray { origin: Point3D::new(0.0, 0.0, 0.0), direction: Vector3 { x: 0.0, y: 0.0, z: 1.0 } };
+        assert!(bvh.closest(&ray, 0.0, f64::INFINITY, |_, _, _| None).is_none());
+    }
+
+    // not a real benchmark harness, just enough to see the difference:
+    // cargo test --release -p raytracer bvh_speedup -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bvh_speedup() {
+        use std::time::Instant;
+
+        // a bumpy 224x224 quad heightfield, ~100k triangles
+        const N: usize = 224;
+        let point = |i: usize, j: usize| {
+            let (x, y) = (i as f64 / N as f64 * 4.0 - 2.0, j as f64 / N as f64 * 4.0 - 2.0);
+            Point3D::new(x, y, 5.0 + 0.2 * (x * 7.0).sin() * (y * 5.0).cos())
+        };
+        let mut triangles = Vec::new();
+        for i in 0..N {
+            for j in 0..N {
+                let (a, b, c, d) = (point(i, j), point(i + 1, j), point(i + 1, j + 1), point(i, j + 1));
+                triangles.push(Triangle::new([a, b, c], Color::WHITE, 0, 0.0));
+                triangles.push(Triangle::new([a, c, d], Color::WHITE, 0, 0.0));
+            }
+        }
+
+        let rays: Vec<Ray> = (0..32 * 32).map(|k| {
+            let (x, y) = ((k % 32) as f64 / 32.0 - 0.5, (k / 32) as f64 / 32.0 - 0.5);
+            Ray { origin: Point3D::new(0.0, 0.0, 0.0), direction: Vector3 { x, y, z: 1.0 } }
+        }).collect();
+
+        let start = Instant::now();
+        let bounds: Vec<Aabb> = triangles.iter().map(|t| t.bounding_box().unwrap()).collect();
+        let bvh = Bvh::build(&bounds);
+        let build = start.elapsed();
+
+        let start = Instant::now();
+        let brute: Vec<Option<f64>> = rays.iter().map(|ray| {
+            let mut t_max = f64::INFINITY;
+            let mut closest = None;
+            for triangle in &triangles {
+                if let Some(hit) = triangle.intersect(ray, 0.001, t_max) {
+                    t_max = hit.t;
+                    closest = Some(hit.t);
+                }
+            }
+            closest
+        }).collect();
+        let linear = start.elapsed();
+
+        let start = Instant::now();
+        let fast: Vec<Option<f64>> = rays.iter().map(|ray| {
+            bvh.closest(ray, 0.001, f64::INFINITY, |i, t_min, t_max| triangles[i].intersect(ray, t_min, t_max))
+                .map(|(_, hit)| hit.t)
+        }).collect();
+        let accelerated = start.elapsed();
+
+        assert_eq!(brute, fast);
+        println!("{} triangles, {} rays", triangles.len(), rays.len());
+        println!("linear: {linear:?}, bvh: {accelerated:?} (built in {build:?}), {:.0}x faster",
+            linear.as_secs_f64() / accelerated.as_secs_f64());
+    }
+}
@@ -0,0 +1,69 @@
+use cg_common::math::{Matrix4, Point3D, Ray, Vector3};
+
+// a pinhole camera. like the book it looks down +z with +y up until it's
+// rotated, and the image plane sits 1 unit in front of it
+#[derive(Debug, Clone, Copy)]
+pub struct Camera {
+    pub position: Point3D,
+    pub orientation: Matrix4, // camera -> world rotation
+    pub fov: f64, // vertical, in radians
+    pub aspect: f64, // width / height of the image
+}
+
+impl Camera {
+    pub fn new(position: Point3D, fov: f64) -> Self {
+        Self { position, orientation: Matrix4::identity(), fov, aspect: 1.0 }
+    }
+
+    // rotate the camera in place so it faces `target`
+    pub fn point_at(&mut self, target: Point3D, up: Vector3) {
+        self.orientation = Matrix4::look_rotation(self.position, target, up);
+    }
+
+    // same camera for an image of a different shape
+    pub fn with_aspect(self, width: u32, height: u32) -> Self {
+        Self { aspect: width as f64 / height.max(1) as f64, ..self }
+    }
+
+    // x and y go from -1 to 1 across the image, +y up. the fov covers the
+    // height and the width follows from the aspect ratio, so nothing stretches
+    pub fn generate_ray(&self, x: f64, y: f64) -> Ray {
+        let half_height = (self.fov / 2.0).tan();
+        let direction = Vector3 { x: x * half_height * self.aspect, y: y * half_height, z: 1.0 };
+
+        Ray { origin: self.position, direction: self.orientation.transform_vector(direction) }
+    }
+}
+
+impl Default for Camera {
+    // the book's 1x1 viewport at distance 1
+    fn default() -> Self {
+        Self::new(Point3D::new(0.0, 0.0, 0.0), 2.0 * 0.5f64.atan())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn center_ray_hits_the_target() {
+        let mut camera = Camera::new(Point3D::new(1.0, 2.0, -3.0), 1.0);
+        let target = Point3D::new(-2.0, 0.5, 4.0);
+        camera.point_at(target, Vector3 { x: 0.0, y: 1.0, z: 0.0 });
+
+        let ray = camera.generate_ray(0.0, 0.0);
+        let to_target = (target - camera.position).normalize();
+        assert!((ray.direction.normalize().dot(to_target) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn wide_images_see_more_not_stretch() {
+        let camera = Camera::default().with_aspect(200, 100);
+        let corner = camera.generate_ray(1.0, 1.0).direction;
+
+        // the book's viewport was 1 high at distance 1, so the top edge is at 0.5
+        assert!((corner.y - 0.5).abs() < 1e-9);
+        assert!((corner.x - 1.0).abs() < 1e-9);
+    }
+}
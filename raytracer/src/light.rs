@@ -35,13 +35,15 @@ impl Light {
         }
     }
 
-    pub fn vector(&self, p: Point3D) -> Option<(Vector3, u32)> {
+    // direction from p towards the light, and how far along it (in multiples
+    // of that direction) the light is. anything closer than that casts a shadow
+    pub fn vector(&self, p: Point3D) -> Option<(Vector3, f64)> {
         match self {
             Light::Point { position, .. } => {
-                Some((*position - p, 1)) 
+                Some((*position - p, 1.0))
             },
             Light::Directional { direction, .. } => {
-                Some((*direction, 100)) // "infinite" for now
+                Some((*direction, f64::INFINITY))
             },
             Light::Ambient { .. } => {
                 None
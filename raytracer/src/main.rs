@@ -1,22 +1,37 @@
+pub mod bvh;
+pub mod camera;
 pub mod primitive;
 pub mod scene;
 pub mod light;
+pub mod options;
+pub mod render;
+pub mod sampling;
+pub mod scene_file;
+pub mod watch;
 
 
-use primitive::Sphere;
 use winit::dpi::LogicalSize;
-use scene::{Scene, Viewport};
-use light::*;
+use render::{BackgroundRender, render};
+use camera::Camera;
+use sampling::Sampling;
+use scene::Scene;
+use scene_file::{DEMO_SCENE, RenderSettings, SceneDescription};
+use options::{Options, USAGE};
+use watch::FileWatcher;
 
-use cg_common::math::{Point3D, Vector3, Point2D};
-use cg_common::canvas::Canvas;
+use cg_common::fly::{FlyCamera, Input};
+use cg_common::framebuffer::Framebuffer;
 
 use std::error::Error;
 use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use winit::application::ApplicationHandler;
-use winit::event::WindowEvent;
-use winit::event_loop::{self, ActiveEventLoop, EventLoop};
+use winit::event::{ElementState, KeyEvent, WindowEvent};
+use winit::event_loop::{self, ActiveEventLoop, EventLoop, EventLoopProxy};
+use winit::keyboard::{KeyCode, PhysicalKey};
 use winit::window::{Window, WindowId};
 
 // comment out for wayland and change event_loop declaration in main()
@@ -25,13 +40,142 @@ use winit::platform::x11::EventLoopBuilderExtX11;
 struct App {
     window: Option<Rc<Window>>,
     surface: Option<softbuffer::Surface<Rc<Window>, Rc<Window>>>,
+    framebuffer: Framebuffer,
+    scene: Arc<Scene>,
+    settings: RenderSettings,
+    dirty: bool, // the framebuffer no longer matches the scene
+    options: Options,
+    watcher: Option<FileWatcher>, // set when rendering a scene file
+    job: Option<BackgroundRender>, // the frame currently being traced
+    started: Instant, // when job began
+    proxy: EventLoopProxy<TileFinished>,
+    fly: FlyCamera,
+    last_update: Instant, // last time fly.update ran
+    last_moved: Option<Instant>, // None once we've settled back to full resolution
 }
 
-impl ApplicationHandler for App {
+// while the camera moves we trace at 1/PREVIEW_SCALE resolution, and go back
+// to full resolution once it's been still for SETTLE. previews skip
+// supersampling too
+const PREVIEW_SCALE: u32 = 4;
+const SETTLE: Duration = Duration::from_millis(200);
+const FRAME: Duration = Duration::from_millis(16);
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+// sent by the render thread whenever it has tiles for us
+struct TileFinished;
+
+impl App {
+    // keeps the last good scene on screen if the new one doesn't load
+    fn reload_scene(&mut self) {
+        match load_scene(&self.options) {
+            Ok(SceneDescription { scene, render: settings, files }) => {
+                if let Some(path) = &self.options.scene {
+                    log::info!("reloaded {}", path.display());
+                    // the scene may have picked up (or dropped) meshes
+                    self.watcher = Some(watch_scene(path, files));
+                }
+                // keep flying from where we are, but R now goes to the file's camera
+                let camera = scene.camera;
+                let fly = FlyCamera::from_orientation(camera.position, camera.orientation, camera.fov);
+                self.fly.set_home(fly);
+                self.scene = Arc::new(scene);
+                // the window is already open, so only the quality settings carry over
+                self.settings = RenderSettings { width: self.settings.width, height: self.settings.height, ..settings };
+                self.dirty = true;
+                if let Some(window) = &self.window {
+                    window.request_redraw();
+                }
+            },
+            Err(e) => log::error!("keeping the previous scene: {e}"),
+        }
+    }
+
+    fn camera(&self) -> Camera {
+        Camera { position: self.fly.position, orientation: self.fly.orientation(), fov: self.fly.fov, aspect: 1.0 }
+    }
+
+    fn camera_changed(&mut self) {
+        self.dirty = true;
+        self.last_moved = Some(Instant::now());
+        if let Some(window) = &self.window {
+            window.request_redraw();
+        }
+    }
+
+    fn previewing(&self) -> bool {
+        self.fly.is_moving() || self.last_moved.is_some_and(|t| t.elapsed() < SETTLE)
+    }
+
+    // throws away whatever was in progress and starts tracing from scratch
+    fn restart_render(&mut self, width: u32, height: u32) {
+        self.job = None; // cancels and waits for the old one
+        self.framebuffer.resize(width, height);
+        self.framebuffer.clear(0);
+
+        let mut quality = self.settings.quality();
+        if self.previewing() {
+            quality.sampling = Sampling::SINGLE;
+        }
+
+        let proxy = self.proxy.clone();
+        self.job = Some(BackgroundRender::start(self.scene.clone(), self.camera(), width, height, quality, move || {
+            let _ = proxy.send_event(TileFinished);
+        }));
+        self.started = Instant::now();
+        self.dirty = false;
+    }
+
+    // copy over finished tiles, and let go of the job once it's done
+    fn collect_tiles(&mut self) {
+        let Some(job) = &mut self.job else {
+            return;
+        };
+        if (job.width, job.height) != (self.framebuffer.width, self.framebuffer.height) {
+            return;
+        }
+
+        job.drain(&mut self.framebuffer.canvas());
+        if job.is_finished() {
+            log::info!("rendered {}x{} in {:.2?}", job.width, job.height, self.started.elapsed());
+            self.job = None;
+        } else if job.is_cancelled() {
+            log::info!("cancelled at {:.0}%", job.progress() * 100.0);
+            self.job = None;
+        }
+    }
+}
+
+// the built-in demo unless --scene says otherwise. flags override the
+// scene's own [render] settings
+fn load_scene(options: &Options) -> Result<SceneDescription, Box<dyn Error>> {
+    let mut description = match &options.scene {
+        Some(path) => scene_file::load(path)?,
+        None => scene_file::parse(DEMO_SCENE, Path::new(""))?,
+    };
+
+    let settings = &mut description.render;
+    settings.width = options.width.unwrap_or(settings.width);
+    settings.height = options.height.unwrap_or(settings.height);
+    settings.recursion_depth = options.recursion_depth.unwrap_or(settings.recursion_depth);
+    settings.samples = options.samples.unwrap_or(settings.samples);
+    settings.pattern = options.pattern.unwrap_or(settings.pattern);
+    settings.filter = options.filter.unwrap_or(settings.filter);
+
+    Ok(description)
+}
+
+// the scene file and every mesh and material it pulled in
+fn watch_scene(path: &Path, mut files: Vec<PathBuf>) -> FileWatcher {
+    files.insert(0, path.to_path_buf());
+    FileWatcher::new(&files, WATCH_INTERVAL)
+}
+
+impl ApplicationHandler<TileFinished> for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         let window_attributes = Window::default_attributes()
             .with_title("phosphorust raytracer")
-            .with_inner_size(LogicalSize::new(600.0, 600.0));
+            .with_inner_size(LogicalSize::new(self.settings.width as f64, self.settings.height as f64));
         let window = Rc::new(event_loop.create_window(window_attributes).unwrap());
         self.window = Some(window.clone());
 
@@ -44,82 +188,112 @@ impl ApplicationHandler for App {
         window.request_redraw();
     }
     
+    fn user_event(&mut self, _event_loop: &ActiveEventLoop, _event: TileFinished) {
+        if let Some(window) = &self.window {
+            window.request_redraw();
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        let now = Instant::now();
+        let mut wake = None;
+
+        if self.fly.is_moving() {
+            if self.fly.update((now - self.last_update).as_secs_f64()) {
+                self.camera_changed();
+            }
+            wake = Some(now + FRAME);
+        } else if let Some(moved) = self.last_moved {
+            if now >= moved + SETTLE {
+                // still for long enough, the next redraw goes back to full resolution
+                self.last_moved = None;
+                if let Some(window) = &self.window {
+                    window.request_redraw();
+                }
+            } else {
+                wake = Some(moved + SETTLE);
+            }
+        }
+        self.last_update = now;
+
+        if let Some(watcher) = &mut self.watcher {
+            let changed = watcher.poll();
+            let next = watcher.next_check();
+            wake = Some(wake.map_or(next, |w| w.min(next)));
+            if changed {
+                self.reload_scene();
+            }
+        }
+
+        event_loop.set_control_flow(match wake {
+            Some(when) => event_loop::ControlFlow::WaitUntil(when),
+            None => event_loop::ControlFlow::Wait,
+        });
+    }
+
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
+        match self.fly.handle_window_event(&event) {
+            Input::Ignored => (),
+            Input::Handled => return,
+            Input::StartedMoving => {
+                self.last_update = Instant::now(); // don't count the time we sat still
+                return;
+            },
+            Input::ViewChanged => {
+                self.camera_changed();
+                return;
+            },
+        }
+
         match event {
             WindowEvent::CloseRequested => {
                 println!("CloseRequested event. Window closed.");
+                self.job = None;
                 event_loop.exit();
             },
+            WindowEvent::Resized(_) => {
+                // RedrawRequested notices the new size and starts over
+                if let Some(window) = &self.window {
+                    window.request_redraw();
+                }
+            },
+            // stop where we are, keeping what's been traced so far
+            WindowEvent::KeyboardInput { event: KeyEvent { physical_key: PhysicalKey::Code(KeyCode::Escape), state: ElementState::Pressed, .. }, .. } => {
+                if let (Some(job), Some(window)) = (&self.job, &self.window) {
+                    job.cancel();
+                    window.request_redraw();
+                }
+            },
             WindowEvent::RedrawRequested => {
-                if let (Some(window), Some(surface)) = (&self.window, &mut self.surface) {
-                    let (width, height) = {
-                        let size = window.inner_size();
-                        (size.width, size.height)
-                    };
-                    
-                    surface
-                        .resize(
-                            NonZeroU32::new(width).unwrap(),
-                            NonZeroU32::new(height).unwrap(),
-                        )
-                        .unwrap();
+                let Some(window) = &self.window else {
+                    return;
+                };
+                let (width, height) = {
+                    let size = window.inner_size();
+                    (size.width, size.height)
+                };
+                let (Some(nz_width), Some(nz_height)) = (NonZeroU32::new(width), NonZeroU32::new(height)) else {
+                    return; // minimized
+                };
+
+                let scale = if self.previewing() { PREVIEW_SCALE } else { 1 };
+                let (render_width, render_height) = ((width / scale).max(1), (height / scale).max(1));
+
+                // only trace when something changed, not on every expose
+                if self.dirty || (render_width, render_height) != (self.framebuffer.width, self.framebuffer.height) {
+                    self.restart_render(render_width, render_height);
+                }
+                self.collect_tiles();
+
+                if let Some(surface) = &mut self.surface {
+                    surface.resize(nz_width, nz_height).unwrap();
 
                     let mut buffer = surface.buffer_mut().unwrap();
-                    buffer.fill(0);
-                    
-                    { // lifetime bullshit 
-                        let mut canvas = Canvas {
-                            width: buffer.width().get(),
-                            height: buffer.height().get(),
-                            buffer: &mut buffer,
-                        };
-
-                        let distance: f64 = 1.0; // normalize distance
-                        let viewrange: usize = 100;
-
-                        let viewport = Viewport { // and viewport extents
-                            width: 1.0,
-                            height: 1.0,
-                        };
-
-                        // draw pixels here
-                        
-                        let mut scene = Scene::new();
-                        scene.add(Box::new(
-                                Sphere::new(Point3D::new(0.0, -1.0, 3.0), 
-                                    1.0, 0xFF0000, 500, 0.2)));
-                        scene.add(Box::new(
-                                Sphere::new(Point3D::new(2.0, 0.0, 4.0), 
-                                    1.0, 0x0000FF, 500, 0.3)));
-                        scene.add(Box::new(
-                                Sphere::new(Point3D::new(-2.0, 0.0, 4.0), 
-                                    1.0, 0x00FF00, 10, 0.4)));
-                        scene.add(Box::new(
-                                Sphere::new(Point3D::new(0.0, -5001.0, 0.0), 
-                                    5000.0, 0xFFFF00, 1000, 0.5)));
-
-                        scene.add_light(Light::new_ambient(0.2));
-                        scene.add_light(Light::new_point(0.6, Point3D::new(2.0, 1.0, 0.0)));
-                        scene.add_light(Light::new_directional(0.2, Vector3 { x: 1.0, y: 4.0, z: 4.0 }));
-
-                        let o = Point3D::new(0.0, 0.0, 0.0); // camera origin
-
-                        for x in -(canvas.width as i32/2)..(canvas.width as i32/2) {
-                            for y in -(canvas.height as i32/2)..(canvas.height as i32/2) {
-                                let viewport_point = Point2D { x: x as f64, y: y as f64 };
-                                let canvas_point = viewport_point.project_viewport(viewport.width,viewport.height,canvas.width, canvas.height, distance);
-                                let d = canvas_point - o;
-                                let color = scene.trace_ray(o, d, distance, viewrange, 3);
-                                canvas.put_pixel(viewport_point, color);
-                            }
-                        }
-
-                        // let p3 = Point3D::new(0.0, 0.0, 0.0); 
-
-                        // let p2 = p3.project2d();
-                        // canvas.put_pixel(p2, 0x6495ED);
+                    if (render_width, render_height) == (width, height) {
+                        self.framebuffer.blit(&mut buffer, width, height);
+                    } else {
+                        self.framebuffer.blit_scaled(&mut buffer, width, height);
                     }
-
                     buffer.present().unwrap();
                 }
             }
@@ -131,13 +305,59 @@ impl ApplicationHandler for App {
 fn main() -> Result<(), Box<dyn Error>> {
     env_logger::init();
 
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+    if options.help {
+        println!("{USAGE}");
+        return Ok(());
+    }
+
+    let SceneDescription { scene, render: settings, files } = match load_scene(&options) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("error: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    // headless: never touches winit, so this works without a display server
+    if let Some(path) = &options.output {
+        let mut framebuffer = Framebuffer::new(settings.width, settings.height);
+        render(&scene, &scene.camera, &mut framebuffer.canvas(), settings.quality());
+        framebuffer.save(path)?;
+        println!("Wrote {}", path.display());
+        return Ok(());
+    }
+
     // Wayland isn't working in WSL2 so we force x11 here. Otherwise we can 
     // let event_loop = EventLoop::event_loop::new()?;
-    let event_loop = EventLoop::builder().with_x11().build()?;
+    let event_loop = EventLoop::with_user_event().with_x11().build()?;
 
-    event_loop.set_control_flow(event_loop::ControlFlow::Poll);
+    // nothing to do between events: the render thread wakes us with TileFinished
+    event_loop.set_control_flow(event_loop::ControlFlow::Wait);
 
-    let mut app = App { window: None, surface: None, };
+    let fly = FlyCamera::from_orientation(scene.camera.position, scene.camera.orientation, scene.camera.fov);
+    let mut app = App {
+        window: None,
+        surface: None,
+        framebuffer: Framebuffer::new(0, 0),
+        scene: Arc::new(scene),
+        settings,
+        dirty: true,
+        watcher: options.scene.as_deref().map(|path| watch_scene(path, files)),
+        options,
+        job: None,
+        started: Instant::now(),
+        proxy: event_loop.create_proxy(),
+        fly,
+        last_update: Instant::now(),
+        last_moved: None,
+    };
     event_loop.run_app(&mut app)?;
 
     Ok(())
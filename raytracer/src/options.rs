@@ -0,0 +1,130 @@
+use std::path::PathBuf;
+
+use crate::sampling::{Filter, Pattern};
+
+pub const USAGE: &str = "\
+usage: raytracer [options]
+
+  -w, --width <px>        image width (default 600)
+  -h, --height <px>       image height (default 600)
+  -d, --depth <n>         reflection recursion depth (default 3)
+      --samples <n>       supersample each pixel n x n times (default 1)
+      --pattern <name>    where the samples go: grid, jittered or rotated
+                          (default grid)
+      --filter <name>     how samples are weighted: box, tent or gaussian
+                          (default box). tent and gaussian reach into the
+                          neighbouring pixels
+  -s, --scene <file>      TOML scene file to render instead of the built-in
+                          demo. its [render] settings replace the defaults
+  -o, --output <file>     render once to a .png, .bmp or .ppm and exit,
+                          without opening a window
+      --help              print this and exit";
+
+// None means "whatever the scene file (or the default) says"
+#[derive(Default)]
+pub struct Options {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub recursion_depth: Option<usize>,
+    pub samples: Option<u32>,
+    pub pattern: Option<Pattern>,
+    pub filter: Option<Filter>,
+    pub scene: Option<PathBuf>,
+    pub output: Option<PathBuf>, // Some means headless
+    pub help: bool,
+}
+
+impl Options {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| args.next().ok_or(format!("{name} needs a value"));
+
+            match arg.as_str() {
+                "-w" | "--width" => options.width = Some(parse_number(&arg, &value(&arg)?)?),
+                "-h" | "--height" => options.height = Some(parse_number(&arg, &value(&arg)?)?),
+                "-d" | "--depth" => options.recursion_depth = Some(parse_number(&arg, &value(&arg)?)?),
+                "--samples" => options.samples = Some(parse_number(&arg, &value(&arg)?)?),
+                "--pattern" => options.pattern = Some(value(&arg)?.parse()?),
+                "--filter" => options.filter = Some(value(&arg)?.parse()?),
+                "-s" | "--scene" => options.scene = Some(PathBuf::from(value(&arg)?)),
+                "-o" | "--output" => options.output = Some(PathBuf::from(value(&arg)?)),
+                "--help" => options.help = true,
+                _ => return Err(format!("unknown argument '{arg}'")),
+            }
+        }
+
+        if options.width == Some(0) || options.height == Some(0) {
+            return Err("width and height must be at least 1".to_string());
+        }
+        if options.samples == Some(0) {
+            return Err("samples must be at least 1".to_string());
+        }
+
+        Ok(options)
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("{name} expects a whole number, got '{value}'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn reads_every_option() {
+        let options = parse(&[
+            "-w", "320", "-h", "200", "-d", "5", "--samples", "3", "--pattern", "rotated",
+            "--filter", "gaussian", "-s", "scenes/demo.toml", "-o", "out.png",
+        ]).unwrap();
+
+        // -h is the height, --help is help
+        assert_eq!((options.width, options.height), (Some(320), Some(200)));
+        assert!(!options.help);
+        assert_eq!(options.recursion_depth, Some(5));
+        assert_eq!(options.samples, Some(3));
+        assert_eq!(options.pattern, Some(Pattern::RotatedGrid));
+        assert_eq!(options.filter, Some(Filter::Gaussian));
+        assert_eq!(options.scene, Some(PathBuf::from("scenes/demo.toml")));
+        assert_eq!(options.output, Some(PathBuf::from("out.png")));
+
+        assert!(parse(&["--help"]).unwrap().help);
+        assert!(parse(&[]).unwrap().width.is_none());
+    }
+
+    #[test]
+    fn pattern_and_filter_names() {
+        for (name, pattern) in [("grid", Pattern::Grid), ("jittered", Pattern::Jittered), ("rotated", Pattern::RotatedGrid)] {
+            assert_eq!(parse(&["--pattern", name]).unwrap().pattern, Some(pattern));
+        }
+        for (name, filter) in [("box", Filter::Box), ("tent", Filter::Tent), ("gaussian", Filter::Gaussian)] {
+            assert_eq!(parse(&["--filter", name]).unwrap().filter, Some(filter));
+        }
+
+        assert!(parse(&["--pattern", "random"]).err().unwrap().contains("unknown sample pattern 'random'"));
+        assert!(parse(&["--filter", "Box"]).err().unwrap().contains("unknown filter 'Box'"));
+    }
+
+    #[test]
+    fn mistakes() {
+        let error = |args: &[&str]| parse(args).err().unwrap();
+
+        assert_eq!(error(&["-h"]), "-h needs a value");
+        assert_eq!(error(&["-w", "300", "--samples"]), "--samples needs a value");
+        assert_eq!(error(&["-w", "wide"]), "-w expects a whole number, got 'wide'");
+        assert_eq!(error(&["-d", "-1"]), "-d expects a whole number, got '-1'");
+        assert_eq!(error(&["--width", "0"]), "width and height must be at least 1");
+        assert_eq!(error(&["-h", "0"]), "width and height must be at least 1");
+        assert_eq!(error(&["--samples", "0"]), "samples must be at least 1");
+        assert_eq!(error(&["--verbose"]), "unknown argument '--verbose'");
+        assert_eq!(error(&["scene.toml"]), "unknown argument 'scene.toml'");
+    }
+}
@@ -1,52 +1,383 @@
-use cg_common::math::{Point3D, Ray, bad_quadratic};
+use std::f64::consts::TAU;
 
-pub trait Primitive {
-    fn intersect(&self, ray: &Ray) -> Option<f64>;
-    fn color(&self) -> u32;
+use cg_common::color::Color;
+use cg_common::math::{Point3D, Ray, Vector3, bad_quadratic, solve_quartic};
+
+use crate::bvh::{Aabb, Bvh};
+
+// everything the shading code needs to know about a ray hitting a surface
+#[derive(Debug, Clone, Copy)]
+pub struct Hit {
+    pub t: f64,
+    pub point: Point3D,
+    pub normal: Vector3, // unit length, always facing back against the ray
+    pub uv: (f64, f64), // surface parameterization, meaning depends on the shape
+    pub front_face: bool, // false if we hit the inside or back of the surface
+}
+
+impl Hit {
+    pub fn new(ray: &Ray, t: f64, outward_normal: Vector3, uv: (f64, f64)) -> Self {
+        let front_face = ray.direction.dot(outward_normal) < 0.0;
+        let normal = if front_face { outward_normal } else { -outward_normal };
+
+        Self { t, point: ray.cast(t), normal, uv, front_face }
+    }
+}
+
+// two unit vectors perpendicular to `axis` and to each other
+fn basis(axis: Vector3) -> (Vector3, Vector3) {
+    let helper = if axis.x.abs() > 0.9 {
+        Vector3 { x: 0.0, y: 1.0, z: 0.0 }
+    } else {
+        Vector3 { x: 1.0, y: 0.0, z: 0.0 }
+    };
+    let u = axis.cross(helper).normalize();
+    (u, axis.cross(u))
+}
+
+// Send + Sync so the render threads can share the scene
+pub trait Primitive: Send + Sync {
+    // nearest hit with t_min <= t <= t_max
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit>;
+    // None for things that go on forever, like Plane
+    fn bounding_box(&self) -> Option<Aabb>;
+    // whether there's any hit in range, for shadows. worth overriding when
+    // finding one hit is cheaper than finding the nearest
+    fn occludes(&self, ray: &Ray, t_min: f64, t_max: f64) -> bool {
+        self.intersect(ray, t_min, t_max).is_some()
+    }
+    fn color(&self) -> Color;
     fn specular(&self) -> u32;
     fn reflective(&self) -> f64;
-    fn get_origin(&self) -> Point3D;
 }
 
 pub struct Sphere {
     origin: Point3D,
     radius: f64,
-    color: u32,
+    color: Color,
     specular: u32,
     reflective: f64,
 }
 
 impl Sphere {
-    pub fn new(origin: Point3D, radius: f64, color: u32, specular: u32, reflective: f64) -> Self {
+    pub fn new(origin: Point3D, radius: f64, color: Color, specular: u32, reflective: f64) -> Self {
         Self { origin, radius, color, specular, reflective }
     }
 }
 
 impl Primitive for Sphere {
     
-    fn intersect(&self, ray: &Ray) -> Option<f64> {
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
         let to_origin = ray.origin - self.origin;
         let a = ray.direction.dot(ray.direction);
         let b = 2.0 * (to_origin.dot(ray.direction));
         let c = (to_origin.dot(to_origin)) - self.radius.powi(2);
 
-        let roots = bad_quadratic(a, b, c);
+        let (t1, t2) = bad_quadratic(a, b, c)?;
+
+        // t1 <= t2, so if the near side is out of range try the far side
+        // (the ray starts inside the sphere)
+        let t = [t1, t2].into_iter().find(|t| (t_min..=t_max).contains(t))?;
+
+        let p = ray.cast(t);
+        let outward = (p - self.origin) * (1.0 / self.radius);
+        let uv = (
+            0.5 + outward.z.atan2(outward.x) / TAU,
+            0.5 + outward.y.clamp(-1.0, 1.0).asin() / std::f64::consts::PI,
+        );
+
+        Some(Hit::new(ray, t, outward, uv))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius;
+        Some(Aabb::around(self.origin, Vector3 { x: r, y: r, z: r }))
+    }
+
+    fn color(&self) -> Color {
+        self.color
+    }
+
+    fn specular(&self) -> u32 {
+        self.specular
+    }
+
+    fn reflective(&self) -> f64 {
+        self.reflective
+    }
+}
+
+
+pub struct Triangle {
+    vertices: [Point3D; 3],
+    normals: Option<[Vector3; 3]>, // per vertex, for smooth shading
+    color: Color,
+    specular: u32,
+    reflective: f64,
+}
+
+impl Triangle {
+    // flat shaded. the front face is the one the vertices go clockwise
+    // around, same as in the rasterizer
+    pub fn new(vertices: [Point3D; 3], color: Color, specular: u32, reflective: f64) -> Self {
+        Self { vertices, normals: None, color, specular, reflective }
+    }
+
+    // normals are interpolated across the face
+    pub fn with_normals(vertices: [Point3D; 3], normals: [Vector3; 3], color: Color, specular: u32, reflective: f64) -> Self {
+        let normals = normals.map(|n| n.normalize());
+        Self { vertices, normals: Some(normals), color, specular, reflective }
+    }
+
+    pub fn face_normal(&self) -> Vector3 {
+        let [a, b, c] = self.vertices;
+        (b - a).cross(c - a).normalize()
+    }
+}
+
+impl Primitive for Triangle {
+    // Möller–Trumbore. uv are the barycentric weights of vertices 1 and 2
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        const EPSILON: f64 = 1e-9;
+        let [a, b, c] = self.vertices;
+        let e1 = b - a;
+        let e2 = c - a;
+
+        let pvec = ray.direction.cross(e2);
+        let det = e1.dot(pvec);
+        if det.abs() < EPSILON {
+            return None; // parallel to the plane, or degenerate
+        }
+        let inv_det = 1.0 / det;
+
+        let tvec = ray.origin - a;
+        let u = tvec.dot(pvec) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let qvec = tvec.cross(e1);
+        let v = ray.direction.dot(qvec) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let t = e2.dot(qvec) * inv_det;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        let face = self.face_normal();
+        let mut hit = Hit::new(ray, t, face, (u, v));
+
+        if let Some([na, nb, nc]) = self.normals {
+            // shade with the interpolated normal, but keep it on the same
+            // side as the geometric one
+            let smooth = (na * (1.0 - u - v) + nb * u + nc * v).normalize();
+            hit.normal = if hit.front_face { smooth } else { -smooth };
+        }
+
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(&self.vertices))
+    }
+
+    fn color(&self) -> Color {
+        self.color
+    }
+
+    fn specular(&self) -> u32 {
+        self.specular
+    }
+
+    fn reflective(&self) -> f64 {
+        self.reflective
+    }
+}
+
+// a pile of triangles sharing one material, e.g. one material's worth of
+// an .obj. the triangles' own colors are ignored
+pub struct TriangleMesh {
+    triangles: Vec<Triangle>,
+    bvh: Bvh,
+    color: Color,
+    specular: u32,
+    reflective: f64,
+}
+
+impl TriangleMesh {
+    pub fn new(triangles: Vec<Triangle>, color: Color, specular: u32, reflective: f64) -> Self {
+        let bounds: Vec<Aabb> = triangles.iter().map(|t| Aabb::from_points(&t.vertices)).collect();
+        let bvh = Bvh::build(&bounds);
+        Self { triangles, bvh, color, specular, reflective }
+    }
+}
+
+impl Primitive for TriangleMesh {
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        self.bvh.closest(ray, t_min, t_max, |i, t_min, t_max| self.triangles[i].intersect(ray, t_min, t_max))
+            .map(|(_, hit)| hit)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bvh.bounds())
+    }
+
+    fn occludes(&self, ray: &Ray, t_min: f64, t_max: f64) -> bool {
+        self.bvh.any(ray, t_min, t_max, |i, t_min, t_max| self.triangles[i].intersect(ray, t_min, t_max).is_some())
+    }
+
+    fn color(&self) -> Color {
+        self.color
+    }
+
+    fn specular(&self) -> u32 {
+        self.specular
+    }
+
+    fn reflective(&self) -> f64 {
+        self.reflective
+    }
+}
+
+// infinite, one point and a normal. both sides are solid
+pub struct Plane {
+    point: Point3D,
+    normal: Vector3,
+    color: Color,
+    specular: u32,
+    reflective: f64,
+}
+
+impl Plane {
+    pub fn new(point: Point3D, normal: Vector3, color: Color, specular: u32, reflective: f64) -> Self {
+        Self { point, normal: normal.normalize(), color, specular, reflective }
+    }
+}
+
+impl Primitive for Plane {
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        let denom = self.normal.dot(ray.direction);
+        if denom.abs() < 1e-9 {
+            return None; // parallel
+        }
+
+        let t = self.normal.dot(self.point - ray.origin) / denom;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        // uv are plain distances along two axes in the plane
+        let (u_axis, v_axis) = basis(self.normal);
+        let offset = ray.cast(t) - self.point;
+
+        Some(Hit::new(ray, t, self.normal, (offset.dot(u_axis), offset.dot(v_axis))))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+
+    fn color(&self) -> Color {
+        self.color
+    }
+
+    fn specular(&self) -> u32 {
+        self.specular
+    }
+
+    fn reflective(&self) -> f64 {
+        self.reflective
+    }
+}
+
+// named so it doesn't shadow std's Box
+pub struct AxisAlignedBox {
+    min: Point3D,
+    max: Point3D,
+    color: Color,
+    specular: u32,
+    reflective: f64,
+}
 
-        match roots { 
-            None => None,
-            Some((t1, t2)) => {
-                if t1 < 0.0 && t2 < 0.0 {
-                    return None; // sphere is behind camera
+impl AxisAlignedBox {
+    // any two opposite corners
+    pub fn new(a: Point3D, b: Point3D, color: Color, specular: u32, reflective: f64) -> Self {
+        let min = Point3D::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z));
+        let max = Point3D::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z));
+        Self { min, max, color, specular, reflective }
+    }
+}
+
+impl Primitive for AxisAlignedBox {
+    // slab test
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        let origin = [ray.origin.x, ray.origin.y, ray.origin.z];
+        let direction = [ray.direction.x, ray.direction.y, ray.direction.z];
+        let min = [self.min.x, self.min.y, self.min.z];
+        let max = [self.max.x, self.max.y, self.max.z];
+
+        // (t, axis, sign of the face normal) for where we enter and leave
+        let mut near = (f64::NEG_INFINITY, 0, 0.0);
+        let mut far = (f64::INFINITY, 0, 0.0);
+
+        for axis in 0..3 {
+            // parallel to this slab: either always inside it or never. doing
+            // the math would give 0 * inf = NaN for rays starting on a face
+            if direction[axis] == 0.0 {
+                if origin[axis] < min[axis] || origin[axis] > max[axis] {
+                    return None;
                 }
+                continue;
+            }
 
-                if t1 < 0.0 { return Some(t2); } // camera is inside the sphere
+            let inv = 1.0 / direction[axis];
+            let mut t0 = (min[axis] - origin[axis]) * inv;
+            let mut t1 = (max[axis] - origin[axis]) * inv;
+            let mut sign = -1.0; // entering through the min face
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+                sign = 1.0;
+            }
 
-                Some(t1.min(t2))
+            if t0 > near.0 {
+                near = (t0, axis, sign);
+            }
+            if t1 < far.0 {
+                far = (t1, axis, -sign);
+            }
+            if near.0 > far.0 {
+                return None;
             }
         }
+
+        // the far face counts if we start inside the box
+        let (t, axis, sign) = [near, far].into_iter()
+            .find(|(t, _, _)| *t >= t_min && *t <= t_max)?;
+
+        let mut normal = [0.0; 3];
+        normal[axis] = sign;
+        let outward = Vector3 { x: normal[0], y: normal[1], z: normal[2] };
+
+        // uv across the face, 0..1 on each of the other two axes
+        let p = ray.cast(t);
+        let p = [p.x, p.y, p.z];
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+        let uv = (
+            (p[a] - min[a]) / (max[a] - min[a]),
+            (p[b] - min[b]) / (max[b] - min[b]),
+        );
+
+        Some(Hit::new(ray, t, outward, uv))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(self.min, self.max))
     }
 
-    fn color(&self) -> u32 {
+    fn color(&self) -> Color {
         self.color
     }
 
@@ -57,9 +388,652 @@ impl Primitive for Sphere {
     fn reflective(&self) -> f64 {
         self.reflective
     }
+}
+
+// flat and round. one point and a normal, like Plane, plus a radius
+pub struct Disk {
+    center: Point3D,
+    normal: Vector3,
+    radius: f64,
+    color: Color,
+    specular: u32,
+    reflective: f64,
+}
 
-    fn get_origin(&self) -> Point3D {
-        self.origin
+impl Disk {
+    pub fn new(center: Point3D, normal: Vector3, radius: f64, color: Color, specular: u32, reflective: f64) -> Self {
+        Self { center, normal: normal.normalize(), radius, color, specular, reflective }
     }
 }
 
+// shared by Disk and the caps of Cylinder and Cone. uv are polar: angle
+// around the normal (0..1) and distance from the center (0..1)
+fn intersect_disk(ray: &Ray, t_min: f64, t_max: f64, center: Point3D, normal: Vector3, radius: f64) -> Option<Hit> {
+    let denom = normal.dot(ray.direction);
+    if denom.abs() < 1e-9 {
+        return None;
+    }
+
+    let t = normal.dot(center - ray.origin) / denom;
+    if t < t_min || t > t_max {
+        return None;
+    }
+
+    let offset = ray.cast(t) - center;
+    let r = offset.len();
+    if r > radius {
+        return None;
+    }
+
+    let (u_axis, v_axis) = basis(normal);
+    let angle = offset.dot(v_axis).atan2(offset.dot(u_axis));
+    Some(Hit::new(ray, t, normal, (0.5 + angle / TAU, r / radius)))
+}
+
+// a disk reaches radius * sin(angle between the normal and the axis) along each axis
+fn disk_bounds(center: Point3D, normal: Vector3, radius: f64) -> Aabb {
+    let extent = |n: f64| radius * (1.0 - n * n).max(0.0).sqrt();
+    Aabb::around(center, Vector3 { x: extent(normal.x), y: extent(normal.y), z: extent(normal.z) })
+}
+
+impl Primitive for Disk {
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        intersect_disk(ray, t_min, t_max, self.center, self.normal, self.radius)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(disk_bounds(self.center, self.normal, self.radius))
+    }
+
+    fn color(&self) -> Color {
+        self.color
+    }
+
+    fn specular(&self) -> u32 {
+        self.specular
+    }
+
+    fn reflective(&self) -> f64 {
+        self.reflective
+    }
+}
+
+// finite and closed at both ends. runs from `base` for `height` along `axis`
+pub struct Cylinder {
+    base: Point3D,
+    axis: Vector3,
+    radius: f64,
+    height: f64,
+    color: Color,
+    specular: u32,
+    reflective: f64,
+}
+
+impl Cylinder {
+    pub fn new(base: Point3D, axis: Vector3, radius: f64, height: f64, color: Color, specular: u32, reflective: f64) -> Self {
+        Self { base, axis: axis.normalize(), radius, height, color, specular, reflective }
+    }
+}
+
+// the closer of two optional hits
+fn nearer(a: Option<Hit>, b: Option<Hit>) -> Option<Hit> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if a.t <= b.t { a } else { b }),
+        (a, b) => a.or(b),
+    }
+}
+
+impl Primitive for Cylinder {
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        // work in the plane perpendicular to the axis, where the side is a circle
+        let oc = ray.origin - self.base;
+        let d_perp = ray.direction + self.axis * -ray.direction.dot(self.axis);
+        let oc_perp = oc + self.axis * -oc.dot(self.axis);
+
+        let a = d_perp.dot(d_perp);
+        let b = 2.0 * d_perp.dot(oc_perp);
+        let c = oc_perp.dot(oc_perp) - self.radius.powi(2);
+
+        // a is zero for rays parallel to the axis, which can only hit the caps
+        let roots = if a > 1e-12 { bad_quadratic(a, b, c) } else { None };
+        let side = roots.and_then(|(t1, t2)| {
+            [t1, t2].into_iter().find_map(|t| {
+                if t < t_min || t > t_max {
+                    return None;
+                }
+                let p = ray.cast(t);
+                let h = (p - self.base).dot(self.axis);
+                if h < 0.0 || h > self.height {
+                    return None;
+                }
+                let radial = (p - self.base) + self.axis * -h;
+                let (u_axis, v_axis) = basis(self.axis);
+                let angle = radial.dot(v_axis).atan2(radial.dot(u_axis));
+                Some(Hit::new(ray, t, radial * (1.0 / self.radius), (0.5 + angle / TAU, h / self.height)))
+            })
+        });
+
+        let top = self.base + self.axis * self.height;
+        let caps = nearer(
+            intersect_disk(ray, t_min, t_max, self.base, -self.axis, self.radius),
+            intersect_disk(ray, t_min, t_max, top, self.axis, self.radius),
+        );
+
+        nearer(side, caps)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let top = self.base + self.axis * self.height;
+        Some(disk_bounds(self.base, self.axis, self.radius).union(disk_bounds(top, self.axis, self.radius)))
+    }
+
+    fn color(&self) -> Color {
+        self.color
+    }
+
+    fn specular(&self) -> u32 {
+        self.specular
+    }
+
+    fn reflective(&self) -> f64 {
+        self.reflective
+    }
+}
+
+// a round base at `base`, narrowing to a point `height` along `axis`.
+// the base is capped
+pub struct Cone {
+    base: Point3D,
+    axis: Vector3,
+    radius: f64,
+    height: f64,
+    color: Color,
+    specular: u32,
+    reflective: f64,
+}
+
+impl Cone {
+    pub fn new(base: Point3D, axis: Vector3, radius: f64, height: f64, color: Color, specular: u32, reflective: f64) -> Self {
+        Self { base, axis: axis.normalize(), radius, height, color, specular, reflective }
+    }
+}
+
+impl Primitive for Cone {
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        let apex = self.base + self.axis * self.height;
+        let v = -self.axis; // from the apex down into the cone
+        let cos2 = self.height.powi(2) / (self.height.powi(2) + self.radius.powi(2));
+
+        let co = ray.origin - apex;
+        let dv = ray.direction.dot(v);
+        let cv = co.dot(v);
+
+        let a = dv * dv - cos2 * ray.direction.dot(ray.direction);
+        let b = 2.0 * (dv * cv - cos2 * ray.direction.dot(co));
+        let c = cv * cv - cos2 * co.dot(co);
+
+        // a is zero for rays parallel to the surface, leaving one root
+        let roots = if a.abs() < 1e-12 {
+            if b.abs() < 1e-12 { None } else { Some((-c / b, -c / b)) }
+        } else {
+            bad_quadratic(a, b, c)
+        };
+
+        let side = roots.and_then(|(t1, t2)| {
+            [t1, t2].into_iter().find_map(|t| {
+                if t < t_min || t > t_max {
+                    return None;
+                }
+                let p = ray.cast(t);
+                let cp = p - apex;
+                let h = cp.dot(v); // distance below the apex, also rejects the mirror cone
+                if h < 0.0 || h > self.height {
+                    return None;
+                }
+                // the gradient vanishes at the tip itself, so just point up the axis there
+                let gradient = cp * cos2 + v * -h;
+                let outward = if gradient.len() > 1e-9 { gradient.normalize() } else { self.axis };
+                let radial = cp + v * -h;
+                let (u_axis, v_axis) = basis(self.axis);
+                let angle = radial.dot(v_axis).atan2(radial.dot(u_axis));
+                Some(Hit::new(ray, t, outward, (0.5 + angle / TAU, 1.0 - h / self.height)))
+            })
+        });
+
+        nearer(side, intersect_disk(ray, t_min, t_max, self.base, -self.axis, self.radius))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let apex = self.base + self.axis * self.height;
+        Some(disk_bounds(self.base, self.axis, self.radius).union(Aabb::new(apex, apex)))
+    }
+
+    fn color(&self) -> Color {
+        self.color
+    }
+
+    fn specular(&self) -> u32 {
+        self.specular
+    }
+
+    fn reflective(&self) -> f64 {
+        self.reflective
+    }
+}
+
+// a ring of `minor_radius` thick tube swept `major_radius` around `axis`
+pub struct Torus {
+    center: Point3D,
+    axis: Vector3,
+    major_radius: f64,
+    minor_radius: f64,
+    color: Color,
+    specular: u32,
+    reflective: f64,
+}
+
+impl Torus {
+    pub fn new(center: Point3D, axis: Vector3, major_radius: f64, minor_radius: f64, color: Color, specular: u32, reflective: f64) -> Self {
+        Self { center, axis: axis.normalize(), major_radius, minor_radius, color, specular, reflective }
+    }
+}
+
+impl Primitive for Torus {
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        let (big_r, small_r) = (self.major_radius, self.minor_radius);
+
+        // cheap reject against the bounding sphere. starting the quartic from
+        // where the ray enters it also keeps the coefficients small, which the
+        // solver needs for precision
+        let bound = big_r + small_r;
+        let oc = ray.origin - self.center;
+        let (s1, s2) = bad_quadratic(
+            ray.direction.dot(ray.direction),
+            2.0 * oc.dot(ray.direction),
+            oc.dot(oc) - bound * bound,
+        )?;
+        if s2 < t_min || s1 > t_max {
+            return None;
+        }
+        let start = s1.max(0.0);
+
+        // local frame where the torus lies in the xz plane around y
+        let (u_axis, w_axis) = basis(self.axis);
+        let to_local = |v: Vector3| Vector3 { x: v.dot(u_axis), y: v.dot(self.axis), z: v.dot(w_axis) };
+        let o = to_local(oc + ray.direction * start);
+        let d = to_local(ray.direction);
+
+        let dd = d.dot(d);
+        let od = o.dot(d);
+        let e = o.dot(o) - big_r * big_r - small_r * small_r;
+        let four_r2 = 4.0 * big_r * big_r;
+
+        let roots = solve_quartic(
+            dd * dd,
+            4.0 * dd * od,
+            2.0 * dd * e + 4.0 * od * od + four_r2 * d.y * d.y,
+            4.0 * od * e + 2.0 * four_r2 * o.y * d.y,
+            e * e - four_r2 * (small_r * small_r - o.y * o.y),
+        );
+
+        let t = roots.into_iter()
+            .map(|t| t + start)
+            .find(|t| *t >= t_min && *t <= t_max)?;
+
+        // gradient of the implicit surface, back in world space
+        let p = o + d * (t - start);
+        let k = p.dot(p) - big_r * big_r - small_r * small_r;
+        let local_normal = Vector3 { x: p.x * k, y: p.y * (k + 2.0 * big_r * big_r), z: p.z * k };
+        let outward = (u_axis * local_normal.x + self.axis * local_normal.y + w_axis * local_normal.z).normalize();
+
+        // u goes around the ring, v around the tube
+        let ring = p.z.atan2(p.x);
+        let tube = p.y.atan2((p.x * p.x + p.z * p.z).sqrt() - big_r);
+        Some(Hit::new(ray, t, outward, (0.5 + ring / TAU, 0.5 + tube / TAU)))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // the center circle's box, fattened by the tube
+        let ring = disk_bounds(self.center, self.axis, self.major_radius);
+        let r = Vector3 { x: self.minor_radius, y: self.minor_radius, z: self.minor_radius };
+        Some(Aabb::new(ring.min + -r, ring.max + r))
+    }
+
+    fn color(&self) -> Color {
+        self.color
+    }
+
+    fn specular(&self) -> u32 {
+        self.specular
+    }
+
+    fn reflective(&self) -> f64 {
+        self.reflective
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ray(origin: (f64, f64, f64), direction: (f64, f64, f64)) -> Ray {
+        Ray { origin: Point3D::new(origin.0, origin.1, origin.2), direction: Vector3 { x: direction.0, y: direction.1, z: direction.2 } }
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    fn normal_is(hit: &Hit, x: f64, y: f64, z: f64) -> bool {
+        let n = Vector3 { x, y, z }.normalize();
+        close(hit.normal.x, n.x) && close(hit.normal.y, n.y) && close(hit.normal.z, n.z)
+    }
+
+    #[test]
+    fn sphere_from_outside_and_inside() {
+        let sphere = Sphere::new(Point3D::new(0.0, 0.0, 3.0), 1.0, Color::WHITE, 0, 0.0);
+
+        let hit = sphere.intersect(&ray((0.0, 0.0, 0.0), (0.0, 0.0, 1.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 2.0) && hit.front_face && normal_is(&hit, 0.0, 0.0, -1.0));
+
+        // from the center the far side is hit from within
+        let hit = sphere.intersect(&ray((0.0, 0.0, 3.0), (0.0, 0.0, 1.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 1.0) && !hit.front_face && normal_is(&hit, 0.0, 0.0, -1.0));
+
+        assert!(sphere.intersect(&ray((0.0, 0.0, 0.0), (1.0, 0.0, 0.0)), 0.001, f64::INFINITY).is_none());
+        assert!(sphere.intersect(&ray((0.0, 0.0, 0.0), (0.0, 0.0, 1.0)), 0.001, 1.5).is_none());
+    }
+
+    // in the z = 1 plane, clockwise seen from the origin so it faces -z
+    fn triangle() -> Triangle {
+        Triangle::new([Point3D::new(0.0, 0.0, 1.0), Point3D::new(0.0, 1.0, 1.0), Point3D::new(1.0, 0.0, 1.0)], Color::WHITE, 0, 0.0)
+    }
+
+    #[test]
+    fn triangle_front_and_back() {
+        let triangle = triangle();
+
+        let hit = triangle.intersect(&ray((0.0, 0.0, 0.0), (0.25, 0.25, 1.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 1.0) && hit.front_face && normal_is(&hit, 0.0, 0.0, -1.0));
+        assert!(close(hit.uv.0, 0.25) && close(hit.uv.1, 0.25));
+
+        let hit = triangle.intersect(&ray((0.25, 0.25, 2.0), (0.0, 0.0, -1.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 1.0) && !hit.front_face && normal_is(&hit, 0.0, 0.0, 1.0));
+
+        assert!(triangle.intersect(&ray((0.0, 0.0, 0.0), (2.0, 2.0, 1.0)), 0.001, f64::INFINITY).is_none());
+        assert!(triangle.intersect(&ray((0.0, 0.0, 0.0), (0.25, 0.25, 1.0)), 0.001, 0.5).is_none());
+    }
+
+    #[test]
+    fn triangle_edges_and_parallel_rays() {
+        let triangle = triangle();
+        let forward = (0.0, 0.0, 1.0);
+
+        // on an edge and on a vertex count, just past the edge doesn't
+        assert!(triangle.intersect(&ray((0.5, 0.0, 0.0), forward), 0.001, f64::INFINITY).is_some());
+        assert!(triangle.intersect(&ray((0.0, 1.0, 0.0), forward), 0.001, f64::INFINITY).is_some());
+        assert!(triangle.intersect(&ray((0.5, -1e-6, 0.0), forward), 0.001, f64::INFINITY).is_none());
+
+        // parallel to the plane, off it and in it
+        assert!(triangle.intersect(&ray((0.0, 0.0, 0.0), (1.0, 0.0, 0.0)), 0.001, f64::INFINITY).is_none());
+        assert!(triangle.intersect(&ray((-1.0, 0.25, 1.0), (1.0, 0.0, 0.0)), 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn triangle_smooth_normals() {
+        let [a, b, c] = triangle().vertices;
+        let normals = [Vector3 { x: 0.0, y: 0.0, z: -1.0 }, Vector3 { x: 0.0, y: 1.0, z: -1.0 }, Vector3 { x: 1.0, y: 0.0, z: -1.0 }];
+        let triangle = Triangle::with_normals([a, b, c], normals, Color::WHITE, 0, 0.0);
+
+        // at a vertex it's that vertex's normal
+        let hit = triangle.intersect(&ray((0.0, 1.0, 0.0), (0.0, 0.0, 1.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(hit.front_face && normal_is(&hit, 0.0, 1.0, -1.0));
+
+        // halfway along b-c it's between theirs, and flipped when seen from behind
+        let hit = triangle.intersect(&ray((0.5, 0.5, 2.0), (0.0, 0.0, -1.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(!hit.front_face && normal_is(&hit, -1.0, -1.0, 2.0));
+    }
+
+    // an 8x8 grid of quads at z = 3 with one small triangle in front of it at z = 2
+    fn mesh_triangles() -> Vec<Triangle> {
+        let mut triangles = Vec::new();
+        for y in -4..4 {
+            for x in -4..4 {
+                let (x, y) = (x as f64, y as f64);
+                let corner = |dx: f64, dy: f64| Point3D::new(x + dx, y + dy, 3.0);
+                triangles.push(Triangle::new([corner(0.0, 0.0), corner(0.0, 1.0), corner(1.0, 0.0)], Color::WHITE, 0, 0.0));
+                triangles.push(Triangle::new([corner(1.0, 0.0), corner(0.0, 1.0), corner(1.0, 1.0)], Color::WHITE, 0, 0.0));
+            }
+        }
+        triangles.push(Triangle::new([Point3D::new(0.0, 0.0, 2.0), Point3D::new(0.0, 1.0, 2.0), Point3D::new(1.0, 0.0, 2.0)], Color::WHITE, 0, 0.0));
+        triangles
+    }
+
+    #[test]
+    fn mesh_finds_the_nearest_triangle() {
+        let loose = mesh_triangles();
+        let mesh = TriangleMesh::new(mesh_triangles(), Color::WHITE, 0, 0.0);
+
+        let hit = mesh.intersect(&ray((0.25, 0.25, 0.0), (0.0, 0.0, 1.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 2.0) && hit.front_face && normal_is(&hit, 0.0, 0.0, -1.0));
+
+        // same answers as testing every triangle
+        for (x, y) in [(-3.5, -3.2), (0.1, 0.1), (0.9, 0.9), (3.9, 2.0), (5.0, 0.0)] {
+            let r = ray((0.0, 0.0, 0.0), (x, y, 3.0));
+            let brute = loose.iter()
+                .filter_map(|t| t.intersect(&r, 0.001, f64::INFINITY))
+                .min_by(|a, b| a.t.total_cmp(&b.t))
+                .map(|h| h.t);
+            assert_eq!(mesh.intersect(&r, 0.001, f64::INFINITY).map(|h| h.t), brute);
+            assert_eq!(mesh.occludes(&r, 0.001, f64::INFINITY), brute.is_some());
+        }
+
+        // occlusion respects the range
+        assert!(!mesh.occludes(&ray((-2.5, -2.5, 0.0), (0.0, 0.0, 1.0)), 0.001, 2.5));
+    }
+
+    #[test]
+    fn plane_from_both_sides() {
+        let plane = Plane::new(Point3D::new(0.0, -1.0, 0.0), Vector3 { x: 0.0, y: 1.0, z: 0.0 }, Color::WHITE, 0, 0.0);
+
+        let hit = plane.intersect(&ray((0.0, 0.0, 0.0), (0.0, -1.0, 1.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 1.0) && hit.front_face && normal_is(&hit, 0.0, 1.0, 0.0));
+
+        let hit = plane.intersect(&ray((0.0, -2.0, 0.0), (0.0, 1.0, 0.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 1.0) && !hit.front_face && normal_is(&hit, 0.0, -1.0, 0.0));
+
+        assert!(plane.intersect(&ray((0.0, 0.0, 0.0), (1.0, 0.0, 0.0)), 0.001, f64::INFINITY).is_none());
+        assert!(plane.intersect(&ray((0.0, 0.0, 0.0), (0.0, 1.0, 0.0)), 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn box_from_outside_and_inside() {
+        let aab = AxisAlignedBox::new(Point3D::new(1.0, 1.0, 4.0), Point3D::new(-1.0, -1.0, 2.0), Color::WHITE, 0, 0.0);
+
+        let hit = aab.intersect(&ray((0.0, 0.0, 0.0), (0.25, 0.0, 1.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 2.0) && hit.front_face && normal_is(&hit, 0.0, 0.0, -1.0));
+        assert!(close(hit.uv.0, 0.75) && close(hit.uv.1, 0.5));
+
+        // through a side face at an angle
+        let hit = aab.intersect(&ray((-3.0, 0.0, 3.0), (1.0, 0.0, 0.25)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 2.0) && normal_is(&hit, -1.0, 0.0, 0.0));
+
+        // from inside only the far face is in range
+        let hit = aab.intersect(&ray((0.0, 0.0, 3.0), (0.0, 0.0, 1.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 1.0) && !hit.front_face && normal_is(&hit, 0.0, 0.0, -1.0));
+
+        assert!(aab.intersect(&ray((0.0, 0.0, 0.0), (1.0, 0.0, 1.0)), 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn box_axis_parallel_rays() {
+        let aab = AxisAlignedBox::new(Point3D::new(-1.0, -1.0, 2.0), Point3D::new(1.0, 1.0, 4.0), Color::WHITE, 0, 0.0);
+
+        // the other two slabs have infinite t ranges
+        let hit = aab.intersect(&ray((0.5, -0.5, 0.0), (0.0, 0.0, 1.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 2.0) && normal_is(&hit, 0.0, 0.0, -1.0));
+        let hit = aab.intersect(&ray((0.5, -0.5, 6.0), (0.0, 0.0, -1.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 2.0) && normal_is(&hit, 0.0, 0.0, 1.0));
+        let hit = aab.intersect(&ray((0.0, 5.0, 3.0), (0.0, -1.0, 0.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 4.0) && normal_is(&hit, 0.0, 1.0, 0.0));
+
+        // and outside a slab they never overlap it
+        assert!(aab.intersect(&ray((2.0, 0.0, 0.0), (0.0, 0.0, 1.0)), 0.001, f64::INFINITY).is_none());
+        assert!(aab.intersect(&ray((0.0, -2.0, 0.0), (0.0, 0.0, 1.0)), 0.001, f64::INFINITY).is_none());
+        assert!(aab.intersect(&ray((0.0, 0.0, 0.0), (1.0, 0.0, 0.0)), 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn box_ray_in_a_face_plane() {
+        let aab = AxisAlignedBox::new(Point3D::new(-1.0, -1.0, 2.0), Point3D::new(1.0, 1.0, 4.0), Color::WHITE, 0, 0.0);
+
+        // skimming along the top face (y = 1) still counts as inside its slab
+        let hit = aab.intersect(&ray((0.0, 1.0, 0.0), (0.0, 0.0, 1.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 2.0) && normal_is(&hit, 0.0, 0.0, -1.0));
+        let hit = aab.intersect(&ray((-3.0, 1.0, 3.0), (1.0, 0.0, 0.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 2.0) && normal_is(&hit, -1.0, 0.0, 0.0));
+
+        // along the edge where two faces meet, parallel to both
+        let hit = aab.intersect(&ray((1.0, 1.0, 0.0), (0.0, 0.0, 1.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 2.0));
+
+        // in the same plane but off to the side of the face
+        assert!(aab.intersect(&ray((2.0, 1.0, 0.0), (0.0, 0.0, 1.0)), 0.001, f64::INFINITY).is_none());
+        assert!(aab.intersect(&ray((0.0, 1.0 + 1e-9, 0.0), (0.0, 0.0, 1.0)), 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn disk_edge() {
+        let disk = Disk::new(Point3D::new(0.0, 0.0, 2.0), Vector3 { x: 0.0, y: 0.0, z: -1.0 }, 1.0, Color::WHITE, 0, 0.0);
+
+        let hit = disk.intersect(&ray((0.999, 0.0, 0.0), (0.0, 0.0, 1.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 2.0) && hit.front_face && normal_is(&hit, 0.0, 0.0, -1.0));
+        assert!(close(hit.uv.1, 0.999));
+        assert!(disk.intersect(&ray((1.001, 0.0, 0.0), (0.0, 0.0, 1.0)), 0.001, f64::INFINITY).is_none());
+        assert!(disk.intersect(&ray((0.0, -1.001, 0.0), (0.0, 0.0, 1.0)), 0.001, f64::INFINITY).is_none());
+
+        let hit = disk.intersect(&ray((0.0, 0.5, 4.0), (0.0, 0.0, -1.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 2.0) && !hit.front_face && normal_is(&hit, 0.0, 0.0, 1.0));
+
+        // edge on
+        assert!(disk.intersect(&ray((-2.0, 0.0, 2.0), (1.0, 0.0, 0.0)), 0.001, f64::INFINITY).is_none());
+    }
+
+    // standing on the xz plane at z = 5, 1 wide and 2 tall
+    fn cylinder() -> Cylinder {
+        Cylinder::new(Point3D::new(0.0, 0.0, 5.0), Vector3 { x: 0.0, y: 1.0, z: 0.0 }, 1.0, 2.0, Color::WHITE, 0, 0.0)
+    }
+
+    #[test]
+    fn cylinder_side_and_caps() {
+        let cylinder = cylinder();
+
+        let hit = cylinder.intersect(&ray((0.0, 1.0, 0.0), (0.0, 0.0, 1.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 4.0) && hit.front_face && normal_is(&hit, 0.0, 0.0, -1.0));
+
+        // from inside, the far wall
+        let hit = cylinder.intersect(&ray((0.0, 1.0, 5.0), (1.0, 0.0, 0.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 1.0) && !hit.front_face && normal_is(&hit, -1.0, 0.0, 0.0));
+
+        // over the top it misses the side, coming down at a slant it lands on the cap
+        assert!(cylinder.intersect(&ray((0.0, 3.0, 0.0), (0.0, 0.0, 1.0)), 0.001, f64::INFINITY).is_none());
+        let hit = cylinder.intersect(&ray((0.0, 3.0, 4.0), (0.0, -1.0, 1.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 1.0) && normal_is(&hit, 0.0, 1.0, 0.0));
+        // and steeper, just past the cap's rim, the side
+        let hit = cylinder.intersect(&ray((0.0, 3.0, 3.4), (0.0, -2.0, 1.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 0.6) && normal_is(&hit, 0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn cylinder_axis_parallel_rays() {
+        let cylinder = cylinder();
+
+        // straight down only the caps can be hit
+        let hit = cylinder.intersect(&ray((0.5, 5.0, 5.0), (0.0, -1.0, 0.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 3.0) && hit.front_face && normal_is(&hit, 0.0, 1.0, 0.0));
+        let hit = cylinder.intersect(&ray((0.0, -3.0, 5.5), (0.0, 1.0, 0.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 3.0) && normal_is(&hit, 0.0, -1.0, 0.0));
+        // from inside, the far cap
+        let hit = cylinder.intersect(&ray((0.0, 1.0, 5.0), (0.0, 1.0, 0.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 1.0) && !hit.front_face);
+
+        assert!(cylinder.intersect(&ray((2.0, 5.0, 5.0), (0.0, -1.0, 0.0)), 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn cone_side_cap_and_apex() {
+        // 45 degree sides, apex at (0, 1, 5)
+        let cone = Cone::new(Point3D::new(0.0, 0.0, 5.0), Vector3 { x: 0.0, y: 1.0, z: 0.0 }, 1.0, 1.0, Color::WHITE, 0, 0.0);
+
+        let hit = cone.intersect(&ray((0.0, 0.5, 0.0), (0.0, 0.0, 1.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 4.5) && hit.front_face && normal_is(&hit, 0.0, 1.0, -1.0));
+
+        let hit = cone.intersect(&ray((0.3, -2.0, 5.0), (0.0, 1.0, 0.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 2.0) && normal_is(&hit, 0.0, -1.0, 0.0));
+
+        // the mirror cone above the apex isn't part of it
+        assert!(cone.intersect(&ray((0.0, 1.5, 0.0), (0.0, 0.0, 1.0)), 0.001, f64::INFINITY).is_none());
+        assert!(cone.intersect(&ray((0.0, -0.5, 0.0), (0.0, 0.0, 1.0)), 0.001, f64::INFINITY).is_none());
+
+        // straight down onto the tip
+        let hit = cone.intersect(&ray((0.0, 3.0, 5.0), (0.0, -1.0, 0.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 2.0) && hit.front_face && normal_is(&hit, 0.0, 1.0, 0.0));
+    }
+
+    // lying flat around (0, 0, 5), tube from 1.5 to 2.5 out
+    fn torus() -> Torus {
+        Torus::new(Point3D::new(0.0, 0.0, 5.0), Vector3 { x: 0.0, y: 1.0, z: 0.0 }, 2.0, 0.5, Color::WHITE, 0, 0.0)
+    }
+
+    #[test]
+    fn torus_hole_and_tube() {
+        let torus = torus();
+
+        // down the middle through the hole
+        assert!(torus.intersect(&ray((0.0, 5.0, 5.0), (0.0, -1.0, 0.0)), 0.001, f64::INFINITY).is_none());
+
+        // down through the tube: in at the top, out at the bottom
+        let down = ray((2.0, 5.0, 5.0), (0.0, -1.0, 0.0));
+        let hit = torus.intersect(&down, 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 4.5) && hit.front_face && normal_is(&hit, 0.0, 1.0, 0.0));
+        let hit = torus.intersect(&down, 4.6, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 5.5) && !hit.front_face && normal_is(&hit, 0.0, 1.0, 0.0));
+
+        // side on, the outside of the tube
+        let hit = torus.intersect(&ray((0.0, 0.0, 0.0), (0.0, 0.0, 1.0)), 0.001, f64::INFINITY).unwrap();
+        assert!(close(hit.t, 2.5) && normal_is(&hit, 0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn torus_grazing_rays() {
+        let torus = torus();
+
+        // just over the top of the tube misses, just under it clips the near side
+        assert!(torus.intersect(&ray((-5.0, 0.501, 5.0), (1.0, 0.0, 0.0)), 0.001, f64::INFINITY).is_none());
+        let hit = torus.intersect(&ray((-5.0, 0.499, 5.0), (1.0, 0.0, 0.0)), 0.001, f64::INFINITY).unwrap();
+        let expected = 3.0 - (0.25f64 - 0.499 * 0.499).sqrt();
+        assert!((hit.t - expected).abs() < 1e-4, "{} vs {expected}", hit.t);
+        assert!(hit.normal.y > 0.99);
+    }
+
+    #[test]
+    fn torus_does_not_shadow_itself() {
+        let torus = torus();
+
+        // off the outer half of the tube along the normal there's nothing
+        // left to hit. a secondary ray finding the surface it started on is
+        // what speckles shadows
+        for i in 0..32 {
+            for j in 0..16 {
+                let ring = i as f64 / 32.0 * TAU;
+                let tube = (j as f64 / 16.0 - 0.5) * 0.95 * std::f64::consts::PI; // -85..85 degrees
+                let normal = Vector3 { x: ring.cos() * tube.cos(), y: tube.sin(), z: ring.sin() * tube.cos() };
+                let point = Point3D::new(2.0 * ring.cos(), 0.0, 5.0 + 2.0 * ring.sin()) + normal * 0.5;
+
+                let away = Ray { origin: point, direction: normal };
+                assert!(torus.intersect(&away, 0.001, f64::INFINITY).is_none(), "ring {i} tube {j}");
+                assert!(!torus.occludes(&away, 0.001, f64::INFINITY));
+            }
+        }
+    }
+}
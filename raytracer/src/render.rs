@@ -0,0 +1,244 @@
+// splits the image into tiles and traces them on every core
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, mpsc};
+use std::thread::{self, JoinHandle};
+
+use cg_common::canvas::Canvas;
+use cg_common::color::Color;
+
+use crate::camera::Camera;
+use crate::sampling::Sampling;
+use crate::scene::Scene;
+
+pub const TILE_SIZE: u32 = 32;
+
+// camera rays give up past this t. t of 1 is the image plane, so it's in
+// multiples of the distance to that
+pub const VIEW_RANGE: f64 = 100.0;
+
+// how much work goes into each pixel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quality {
+    pub recursion_depth: usize,
+    pub sampling: Sampling,
+}
+
+// a rectangle of the image in screen coordinates (top left origin)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// row by row from the top left. edge tiles are cut to fit
+pub fn tiles(width: u32, height: u32) -> Vec<Tile> {
+    let mut tiles = Vec::new();
+    for y in (0..height).step_by(TILE_SIZE as usize) {
+        for x in (0..width).step_by(TILE_SIZE as usize) {
+            tiles.push(Tile { x, y, width: TILE_SIZE.min(width - x), height: TILE_SIZE.min(height - y) });
+        }
+    }
+    tiles
+}
+
+// the tile's pixels, row-major, for an image of width x height
+pub fn render_tile(scene: &Scene, camera: &Camera, tile: &Tile, width: u32, height: u32, quality: Quality) -> Vec<u32> {
+    let camera = camera.with_aspect(width, height);
+    let mut pixels = Vec::with_capacity((tile.width * tile.height) as usize);
+    let mut samples = Vec::new();
+
+    for sy in tile.y..tile.y + tile.height {
+        for sx in tile.x..tile.x + tile.width {
+            quality.sampling.pixel_samples(sx, sy, &mut samples);
+
+            let mut sum = Color::BLACK;
+            let mut total = 0.0;
+            for &(dx, dy, weight) in &samples {
+                // offset from the middle of the pixel, -1..1 across the image
+                let x = 2.0 * (sx as f64 + 0.5 + dx) / width as f64 - 1.0;
+                let y = 1.0 - 2.0 * (sy as f64 + 0.5 + dy) / height as f64;
+                let ray = camera.generate_ray(x, y);
+                // t of 1 is the image plane, like the book nothing in front of it shows
+                sum += scene.trace_ray(&ray, 1.0, VIEW_RANGE, quality.recursion_depth) * weight;
+                total += weight;
+            }
+
+            // only now does it become a framebuffer pixel
+            pixels.push((sum / total).to_u32());
+        }
+    }
+
+    pixels
+}
+
+// traces every tile on a pool of scoped threads and hands each one to
+// `on_tile` on the calling thread as it finishes, in no particular order.
+// setting `cancel` stops the workers after the tiles they're on
+pub fn render_tiles(scene: &Scene, camera: &Camera, width: u32, height: u32, quality: Quality, cancel: &AtomicBool, mut on_tile: impl FnMut(&Tile, &[u32])) {
+    let tiles = tiles(width, height);
+    let next = AtomicUsize::new(0);
+    let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1).min(tiles.len());
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|s| {
+        for _ in 0..threads {
+            let sender = sender.clone();
+            let (tiles, next) = (&tiles, &next);
+            s.spawn(move || {
+                // workers grab the next unclaimed tile until there are none left
+                while let Some(tile) = tiles.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    if cancel.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    let pixels = render_tile(scene, camera, tile, width, height, quality);
+                    if sender.send((*tile, pixels)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender); // so the receiver ends once every worker has
+
+        for (tile, pixels) in receiver {
+            on_tile(&tile, &pixels);
+        }
+    });
+}
+
+fn put_tile(canvas: &mut Canvas, tile: &Tile, pixels: &[u32]) {
+    for (row, line) in pixels.chunks(tile.width as usize).enumerate() {
+        for (column, color) in line.iter().enumerate() {
+            canvas.put_pixel_screen((tile.x as usize + column) as i64, (tile.y as usize + row) as i64, *color);
+        }
+    }
+}
+
+// the whole frame, blocking until it's done
+pub fn render(scene: &Scene, camera: &Camera, canvas: &mut Canvas, quality: Quality) {
+    let cancel = AtomicBool::new(false);
+    render_tiles(scene, camera, canvas.width, canvas.height, quality, &cancel, |tile, pixels| {
+        put_tile(canvas, tile, pixels);
+    });
+}
+
+// a frame rendering on its own thread so the window stays responsive.
+// finished tiles queue up until drain() copies them into a canvas.
+// dropping it cancels the render
+pub struct BackgroundRender {
+    pub width: u32,
+    pub height: u32,
+    cancel: Arc<AtomicBool>,
+    receiver: mpsc::Receiver<(Tile, Vec<u32>)>,
+    thread: Option<JoinHandle<()>>,
+    remaining: usize, // tiles not drained yet
+}
+
+impl BackgroundRender {
+    // `wake` runs on the render thread after every tile, to poke the event loop
+    pub fn start(scene: Arc<Scene>, camera: Camera, width: u32, height: u32, quality: Quality, wake: impl Fn() + Send + 'static) -> Self {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel();
+
+        let thread = {
+            let cancel = cancel.clone();
+            thread::spawn(move || {
+                render_tiles(&scene, &camera, width, height, quality, &cancel, |tile, pixels| {
+                    // a closed channel means we were dropped, the workers
+                    // will have seen cancel too
+                    if sender.send((*tile, pixels.to_vec())).is_ok() {
+                        wake();
+                    }
+                });
+            })
+        };
+
+        Self { width, height, cancel, receiver, thread: Some(thread), remaining: tiles(width, height).len() }
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+
+    // every tile has been drained
+    pub fn is_finished(&self) -> bool {
+        self.remaining == 0
+    }
+
+    // copies whatever has finished since the last call into `canvas`, which
+    // should be the size the render was started at. returns the tile count
+    pub fn drain(&mut self, canvas: &mut Canvas) -> usize {
+        let mut drained = 0;
+        for (tile, pixels) in self.receiver.try_iter() {
+            put_tile(canvas, &tile, &pixels);
+            drained += 1;
+        }
+        self.remaining -= drained;
+        drained
+    }
+
+    // 0 to 1
+    pub fn progress(&self) -> f64 {
+        let total = tiles(self.width, self.height).len();
+        if total == 0 { 1.0 } else { 1.0 - self.remaining as f64 / total as f64 }
+    }
+}
+
+impl Drop for BackgroundRender {
+    fn drop(&mut self) {
+        self.cancel();
+        // workers finish the tile they're on, which doesn't take long
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_cover_the_image_once() {
+        let (width, height) = (100, 70);
+        let mut covered = vec![0; (width * height) as usize];
+
+        for tile in tiles(width, height) {
+            for y in tile.y..tile.y + tile.height {
+                for x in tile.x..tile.x + tile.width {
+                    covered[(y * width + x) as usize] += 1;
+                }
+            }
+        }
+
+        assert!(covered.iter().all(|c| *c == 1));
+    }
+
+    #[test]
+    fn background_matches_blocking() {
+        let scene = Arc::new(crate::scene_file::parse(crate::scene_file::DEMO_SCENE, std::path::Path::new("")).unwrap().scene);
+        let (width, height) = (40, 30);
+        // jittered, to check the samples don't depend on which thread takes a tile
+        let sampling = Sampling { samples: 2, pattern: crate::sampling::Pattern::Jittered, filter: crate::sampling::Filter::Tent };
+        let quality = Quality { recursion_depth: 1, sampling };
+
+        let mut expected = vec![0; (width * height) as usize];
+        render(&scene, &scene.camera, &mut Canvas { buffer: &mut expected, depth: None, width, height }, quality);
+
+        let camera = scene.camera;
+        let mut job = BackgroundRender::start(scene, camera, width, height, quality, || {});
+        let mut actual = vec![0; (width * height) as usize];
+        while !job.is_finished() {
+            job.drain(&mut Canvas { buffer: &mut actual, depth: None, width, height });
+            thread::yield_now();
+        }
+
+        assert_eq!(expected, actual);
+    }
+}
@@ -0,0 +1,178 @@
+// where inside (and around) a pixel the rays go, and how much each counts
+
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pattern {
+    #[default]
+    Grid, // evenly spaced, n x n
+    Jittered, // one random spot in each cell of the grid
+    #[serde(rename = "rotated")]
+    RotatedGrid, // the grid turned about 26.6 degrees, so no two samples share a row or column
+}
+
+// reconstruction filters. the wider ones reach into the neighbouring pixels,
+// which softens edges more but aliases less
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Filter {
+    #[default]
+    Box,
+    Tent,
+    Gaussian,
+}
+
+impl FromStr for Pattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "grid" => Ok(Pattern::Grid),
+            "jittered" => Ok(Pattern::Jittered),
+            "rotated" => Ok(Pattern::RotatedGrid),
+            _ => Err(format!("unknown sample pattern '{s}', expected grid, jittered or rotated")),
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "box" => Ok(Filter::Box),
+            "tent" => Ok(Filter::Tent),
+            "gaussian" => Ok(Filter::Gaussian),
+            _ => Err(format!("unknown filter '{s}', expected box, tent or gaussian")),
+        }
+    }
+}
+
+impl Filter {
+    // how far from the pixel center samples go, in pixels
+    pub fn radius(&self) -> f64 {
+        match self {
+            Filter::Box => 0.5,
+            Filter::Tent => 1.0,
+            Filter::Gaussian => 1.5,
+        }
+    }
+
+    // dx, dy are from the pixel center, in pixels
+    pub fn weight(&self, dx: f64, dy: f64) -> f64 {
+        let r = self.radius();
+        match self {
+            Filter::Box => 1.0,
+            Filter::Tent => (1.0 - dx.abs() / r).max(0.0) * (1.0 - dy.abs() / r).max(0.0),
+            Filter::Gaussian => {
+                // shifted down so it reaches exactly 0 at the radius
+                let g = |d: f64| ((-2.0 * d * d).exp() - (-2.0 * r * r).exp()).max(0.0);
+                g(dx) * g(dy)
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sampling {
+    pub samples: u32, // per side, so samples^2 rays a pixel
+    pub pattern: Pattern,
+    pub filter: Filter,
+}
+
+impl Sampling {
+    // one ray through the middle of each pixel
+    pub const SINGLE: Self = Self { samples: 1, pattern: Pattern::Grid, filter: Filter::Box };
+
+    // fills `out` with (dx, dy, weight) for pixel (x, y), offsets in pixels from
+    // its center. jitter is seeded by the pixel, so a frame always comes out the
+    // same however it's split across threads
+    pub fn pixel_samples(&self, x: u32, y: u32, out: &mut Vec<(f64, f64, f64)>) {
+        out.clear();
+        let n = self.samples.max(1);
+        if n == 1 {
+            out.push((0.0, 0.0, 1.0));
+            return;
+        }
+
+        let cell = 1.0 / n as f64;
+        let (sin, cos) = 0.5f64.atan().sin_cos();
+        let footprint = 2.0 * self.filter.radius();
+
+        for j in 0..n {
+            for i in 0..n {
+                // somewhere in the unit square
+                let (u, v) = match self.pattern {
+                    Pattern::Grid => ((i as f64 + 0.5) * cell, (j as f64 + 0.5) * cell),
+                    Pattern::Jittered => {
+                        let seed = hash(x, y, j * n + i);
+                        ((i as f64 + unit(seed)) * cell, (j as f64 + unit(seed >> 32)) * cell)
+                    },
+                    Pattern::RotatedGrid => {
+                        let (gx, gy) = ((i as f64 + 0.5) * cell - 0.5, (j as f64 + 0.5) * cell - 0.5);
+                        // turned about the center and wrapped back into the square
+                        ((gx * cos - gy * sin + 0.5).rem_euclid(1.0), (gx * sin + gy * cos + 0.5).rem_euclid(1.0))
+                    },
+                };
+
+                let (dx, dy) = ((u - 0.5) * footprint, (v - 0.5) * footprint);
+                out.push((dx, dy, self.filter.weight(dx, dy)));
+            }
+        }
+    }
+}
+
+impl Default for Sampling {
+    fn default() -> Self {
+        Self::SINGLE
+    }
+}
+
+// splitmix64 over the pixel and sample index
+fn hash(x: u32, y: u32, i: u32) -> u64 {
+    let mut z = ((x as u64) << 40 ^ (y as u64) << 20 ^ i as u64).wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// the low 32 bits as a number in [0, 1)
+fn unit(bits: u64) -> f64 {
+    (bits & 0xFFFF_FFFF) as f64 / (1u64 << 32) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_stay_in_the_footprint() {
+        let mut out = Vec::new();
+        for pattern in [Pattern::Grid, Pattern::Jittered, Pattern::RotatedGrid] {
+            for filter in [Filter::Box, Filter::Tent, Filter::Gaussian] {
+                let sampling = Sampling { samples: 4, pattern, filter };
+                sampling.pixel_samples(17, 3, &mut out);
+
+                assert_eq!(out.len(), 16);
+                let r = filter.radius();
+                assert!(out.iter().all(|(dx, dy, w)| dx.abs() <= r && dy.abs() <= r && *w >= 0.0));
+                assert!(out.iter().map(|s| s.2).sum::<f64>() > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn rotated_grid_has_no_shared_rows() {
+        let mut out = Vec::new();
+        Sampling { samples: 2, pattern: Pattern::RotatedGrid, filter: Filter::Box }.pixel_samples(0, 0, &mut out);
+
+        for (a, sa) in out.iter().enumerate() {
+            for sb in &out[a + 1..] {
+                assert!((sa.0 - sb.0).abs() > 1e-6 && (sa.1 - sb.1).abs() > 1e-6);
+            }
+        }
+    }
+}
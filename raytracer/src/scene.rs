@@ -1,26 +1,59 @@
-use cg_common::math::{Point3D, Ray, Vector3, apply_intensity};
-use crate::primitive::Primitive;
+use cg_common::color::Color;
+use cg_common::math::{Point3D, Ray, Vector3};
+use crate::bvh::{Aabb, Bvh};
+use crate::camera::Camera;
+use crate::primitive::{Hit, Primitive};
 use crate::light::*;
 
+// the hierarchy over everything with a bounding box, plus the few things
+// without one (planes) that every ray has to be tested against anyway
+struct Acceleration {
+    bvh: Bvh,
+    bounded: Vec<usize>, // bvh item -> index into Scene::objects
+    unbounded: Vec<usize>,
+}
+
 pub struct Scene {
-    pub origin: Point3D,
+    pub camera: Camera,
     pub objects: Vec<Box<dyn Primitive>>,
     pub lights: Vec<Light>,
-    pub background_color: u32,
+    pub background_color: Color,
+    acceleration: Option<Acceleration>, // None until build_bvh, and after any add
 }
 
 impl Scene {
     pub fn new() -> Self {
         Self { 
-            origin: Point3D { x: 0.0, y: 0.0, z: 0.0 },
+            camera: Camera::default(),
             objects: Vec::new(),
             lights: Vec::new(),
-            background_color: 0x101010,
+            background_color: Color::from_u32(0x101010),
+            acceleration: None,
         }
     }
 
     pub fn add(&mut self, object: Box<dyn Primitive>) {
         self.objects.push(object);
+        self.acceleration = None;
+    }
+
+    // call once all the objects are in. until then every ray tests every object
+    pub fn build_bvh(&mut self) {
+        let mut bounds: Vec<Aabb> = Vec::new();
+        let mut bounded = Vec::new();
+        let mut unbounded = Vec::new();
+
+        for (i, object) in self.objects.iter().enumerate() {
+            match object.bounding_box() {
+                Some(b) => {
+                    bounds.push(b);
+                    bounded.push(i);
+                },
+                None => unbounded.push(i),
+            }
+        }
+
+        self.acceleration = Some(Acceleration { bvh: Bvh::build(&bounds), bounded, unbounded });
     }
 
     pub fn add_light(&mut self, light: Light) {
@@ -31,7 +64,8 @@ impl Scene {
         n * 2.0 * n.dot(r) + (-r)
     }
 
-    pub fn compute_lighting(&self, p: Point3D, n: Vector3, vv: Vector3, s: u32) -> f64 {
+    pub fn compute_lighting(&self, hit: &Hit, vv: Vector3, s: u32) -> f64 {
+        let (p, n) = (hit.point, hit.normal);
         let mut i = 0.0;
         for light in &self.lights {
             if let Light::Ambient { intensity } = light {
@@ -41,11 +75,8 @@ impl Scene {
 
             if let Some((l,t_max)) = light.vector(p) {
                 // shadows
-                let (_shadow_t,_ , shadow_object) = self.closest_intersection(p, l, 0.001, t_max as usize);
-
-                match shadow_object {
-                    Some(_o) => continue,
-                    None => {},
+                if self.occluded(p, l, 0.001, t_max) {
+                    continue;
                 }
                 
                 let m = n.dot(l);
@@ -66,68 +97,175 @@ impl Scene {
         i
     }
 
-    pub fn closest_intersection(&self, o: Point3D, d: Vector3, distance: f64, viewrange: usize) -> (f64, Ray, Option<&Box<dyn Primitive>>) {
-        let mut closest_t = viewrange as f64;
-        let ray = Ray { origin: o, direction: d };
-        let mut closest_object: Option<&Box<dyn Primitive>> = None;
+    pub fn closest_intersection(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(Hit, &dyn Primitive)> {
+        let mut closest: Option<(Hit, &dyn Primitive)> = None;
+        let mut closest_t = t_max;
 
-        for object in &self.objects {
-            
-            let t = object.intersect(&ray);
+        // shrinking t_max as we go lets objects skip hits behind the current best
+        let mut test = |i: usize| {
+            if let Some(hit) = self.objects[i].intersect(ray, t_min, closest_t) {
+                closest_t = hit.t;
+                closest = Some((hit, self.objects[i].as_ref()));
+            }
+        };
+        match &self.acceleration {
+            Some(acceleration) => acceleration.unbounded.iter().for_each(|&i| test(i)),
+            None => (0..self.objects.len()).for_each(test),
+        }
 
-            match t {
-                Some(t) => { 
-                    if (distance <= t && t <= viewrange as f64) && t < closest_t {
-                        closest_t = t;
-                        closest_object = Some(object);
-                    }
-                }
-                None => { /* don't do anything lol*/ }
+        if let Some(acceleration) = &self.acceleration {
+            let found = acceleration.bvh.closest(ray, t_min, closest_t, |i, t_min, t_max| {
+                self.objects[acceleration.bounded[i]].intersect(ray, t_min, t_max)
+            });
+            if let Some((i, hit)) = found {
+                closest = Some((hit, self.objects[acceleration.bounded[i]].as_ref()));
             }
         }
-        (closest_t, ray, closest_object)
+
+        closest
     }
 
-    pub fn trace_ray(&self, o: Point3D, d: Vector3, distance: f64, viewrange: usize, recursion_depth: usize) -> u32 {
-        let (closest_t, ray, closest_object) = 
-            self.closest_intersection(o, d, distance, viewrange);
+    // true if anything at all is between t_min and t_max along the ray.
+    // cheaper than closest_intersection since the first hit will do
+    pub fn occluded(&self, origin: Point3D, direction: Vector3, t_min: f64, t_max: f64) -> bool {
+        let ray = Ray { origin, direction };
+
+        match &self.acceleration {
+            Some(acceleration) => {
+                acceleration.unbounded.iter().any(|&i| self.objects[i].occludes(&ray, t_min, t_max))
+                    || acceleration.bvh.any(&ray, t_min, t_max, |i, t_min, t_max| {
+                        self.objects[acceleration.bounded[i]].occludes(&ray, t_min, t_max)
+                    })
+            },
+            None => self.objects.iter().any(|object| object.occludes(&ray, t_min, t_max)),
+        }
+    }
 
-        match closest_object {
+    pub fn trace_ray(&self, ray: &Ray, t_min: f64, t_max: f64, recursion_depth: usize) -> Color {
+        match self.closest_intersection(ray, t_min, t_max) {
             None => self.background_color,
-            Some(object) => { 
-                let p = o + (ray.direction * closest_t);
-                let mut n = p - object.get_origin();
-                n = n.normalize();
-                let local_color = apply_intensity(object.color(), 
-                    self.compute_lighting(p, n, -ray.direction, object.specular()));
+            Some((hit, object)) => { 
+                let local_color = object.color() * self.compute_lighting(&hit, -ray.direction, object.specular());
                 let reflectivity = object.reflective();
-                if recursion_depth <= 0 || reflectivity <= 0.0 {
+                if recursion_depth == 0 || reflectivity <= 0.0 {
                     return local_color;
                 }
 
-                let r = self.reflect_ray(-ray.direction, n);
-                let reflected_color = self.trace_ray(p, r, 0.001, viewrange, recursion_depth - 1);
+                let r = self.reflect_ray(-ray.direction, hit.normal);
+                let reflected = Ray { origin: hit.point, direction: r };
+                let reflected_color = self.trace_ray(&reflected, 0.001, t_max, recursion_depth - 1);
 
-                add_colors(
-                    apply_intensity(local_color, 1.0 - reflectivity),
-                    apply_intensity(reflected_color,reflectivity)
-                )
+                local_color.lerp(reflected_color, reflectivity)
             },
         }
+    }
+}
 
-        
+impl Default for Scene {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-fn add_colors(color_a: u32, color_b: u32) -> u32 {
-    let new_r = (((color_a >> 16) & 0xFF) + ((color_b >> 16) & 0xFF)).min(255);
-    let new_g = (((color_a >> 8) & 0xFF) + ((color_b >> 8) & 0xFF)).min(255);
-    let new_b = (((color_a) & 0xFF) + ((color_b) & 0xFF)).min(255);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitive::{Plane, Sphere, Triangle, TriangleMesh};
 
-    new_r << 16 | new_g << 8 | new_b 
-}
+    fn point(x: f64, y: f64, z: f64) -> Point3D {
+        Point3D::new(x, y, z)
+    }
+
+    fn up() -> Vector3 {
+        Vector3 { x: 0.0, y: 1.0, z: 0.0 }
+    }
 
-pub struct Viewport {
-    pub width: f64,
-    pub height: f64,
+    // a small square facing down, as two triangles, at height y
+    fn square(y: f64) -> TriangleMesh {
+        let corner = |x: f64, z: f64| point(x, y, z);
+        let triangles = vec![
+            Triangle::new([corner(-1.0, -1.0), corner(1.0, -1.0), corner(-1.0, 1.0)], Color::WHITE, 0, 0.0),
+            Triangle::new([corner(1.0, -1.0), corner(1.0, 1.0), corner(-1.0, 1.0)], Color::WHITE, 0, 0.0),
+        ];
+        TriangleMesh::new(triangles, Color::WHITE, 0, 0.0)
+    }
+
+    // the same scene tested object by object, then through the bvh
+    fn both_ways(scene: &mut Scene, check: impl Fn(&Scene)) {
+        check(scene);
+        scene.build_bvh();
+        check(scene);
+    }
+
+    #[test]
+    fn shadows_stop_at_a_point_light() {
+        let light = Light::new_point(1.0, point(0.0, 5.0, 0.0));
+        let p = point(0.0, 0.0, 0.0);
+        let (l, t_max) = light.vector(p).unwrap();
+        assert_eq!(t_max, 1.0);
+
+        let mut between = Scene::new();
+        between.add(Box::new(Sphere::new(point(0.0, 2.5, 0.0), 0.5, Color::WHITE, 0, 0.0)));
+        both_ways(&mut between, |scene| assert!(scene.occluded(p, l, 0.001, t_max)));
+
+        // past the light, so it can't block it
+        let mut beyond = Scene::new();
+        beyond.add(Box::new(Sphere::new(point(0.0, 8.0, 0.0), 0.5, Color::WHITE, 0, 0.0)));
+        both_ways(&mut beyond, |scene| assert!(!scene.occluded(p, l, 0.001, t_max)));
+
+        // and whether it's lit follows from that
+        let hit = Hit { t: 1.0, point: p, normal: up(), uv: (0.0, 0.0), front_face: true };
+        between.add_light(Light::new_point(1.0, point(0.0, 5.0, 0.0)));
+        beyond.add_light(Light::new_point(1.0, point(0.0, 5.0, 0.0)));
+        assert_eq!(between.compute_lighting(&hit, up(), 0), 0.0);
+        assert_eq!(beyond.compute_lighting(&hit, up(), 0), 1.0);
+    }
+
+    #[test]
+    fn directional_shadows_go_on_forever() {
+        let light = Light::new_directional(1.0, up());
+        let p = point(0.0, 0.0, 0.0);
+        let (l, t_max) = light.vector(p).unwrap();
+        assert_eq!(t_max, f64::INFINITY);
+
+        // the same sphere a point light at y = 5 would shine past
+        let mut far = Scene::new();
+        far.add(Box::new(Sphere::new(point(0.0, 8.0, 0.0), 0.5, Color::WHITE, 0, 0.0)));
+        both_ways(&mut far, |scene| assert!(scene.occluded(p, l, 0.001, t_max)));
+
+        // planes skip the bvh, so they're checked on their own
+        let mut ceiling = Scene::new();
+        ceiling.add(Box::new(Plane::new(point(0.0, 100.0, 0.0), -up(), Color::WHITE, 0, 0.0)));
+        both_ways(&mut ceiling, |scene| {
+            assert!(scene.occluded(p, l, 0.001, t_max));
+            assert!(!scene.occluded(p, -l, 0.001, t_max));
+        });
+
+        // the surface itself is inside t_min, so it doesn't count
+        let mut floor = Scene::new();
+        floor.add(Box::new(Plane::new(p, up(), Color::WHITE, 0, 0.0)));
+        both_ways(&mut floor, |scene| assert!(!scene.occluded(p, l, 0.001, t_max)));
+    }
+
+    #[test]
+    fn meshes_cast_shadows() {
+        let p = point(0.5, 0.0, 0.25);
+        let (l, t_max) = Light::new_point(1.0, point(0.5, 4.0, 0.25)).vector(p).unwrap();
+
+        // TriangleMesh::occludes on its own, in and out of range
+        let ray = Ray { origin: p, direction: l };
+        assert!(square(2.0).occludes(&ray, 0.001, t_max));
+        assert!(!square(6.0).occludes(&ray, 0.001, t_max));
+        assert!(square(6.0).occludes(&ray, 0.001, f64::INFINITY));
+        assert!(!square(2.0).occludes(&ray, 0.6, t_max));
+        assert!(!square(2.0).occludes(&Ray { origin: point(3.0, 0.0, 0.0), direction: l }, 0.001, t_max));
+
+        let mut between = Scene::new();
+        between.add(Box::new(square(2.0)));
+        both_ways(&mut between, |scene| assert!(scene.occluded(p, l, 0.001, t_max)));
+
+        let mut beyond = Scene::new();
+        beyond.add(Box::new(square(6.0)));
+        both_ways(&mut beyond, |scene| assert!(!scene.occluded(p, l, 0.001, t_max)));
+    }
 }
@@ -0,0 +1,635 @@
+// loads a Scene from a TOML description. see scenes/demo.toml for the format
+
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use serde::de::{self, DeserializeOwned, Deserializer, Visitor};
+use toml::Spanned;
+use toml::de::{DeTable, DeValue, ValueDeserializer};
+
+use cg_common::color::Color;
+use cg_common::math::{Matrix4, Point3D, Vector3};
+use cg_common::obj::{Material, Mesh};
+
+use crate::light::Light;
+use crate::primitive::{AxisAlignedBox, Cone, Cylinder, Disk, Plane, Primitive, Sphere, Torus, Triangle, TriangleMesh};
+use crate::camera::Camera;
+use crate::render::Quality;
+use crate::sampling::{Filter, Pattern, Sampling};
+use crate::scene::Scene;
+
+pub const DEMO_SCENE: &str = include_str!("../scenes/demo.toml");
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io { path: PathBuf, source: std::io::Error },
+    Parse { line: usize, column: usize, message: String },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io { path, source } => write!(f, "couldn't read {}: {source}", path.display()),
+            SceneError::Parse { line, column, message } => write!(f, "line {line}, column {column}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneError::Io { source, .. } => Some(source),
+            SceneError::Parse { .. } => None,
+        }
+    }
+}
+
+impl SceneError {
+    fn at(src: &str, span: Option<Range<usize>>, message: impl Into<String>) -> Self {
+        let offset = span.map(|s| s.start).unwrap_or(0).min(src.len());
+        let before = &src[..offset];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map(|i| i + 1).unwrap_or(0) + 1;
+
+        SceneError::Parse { line, column, message: message.into() }
+    }
+}
+
+// render settings a scene can ask for. command-line flags win over these
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderSettings {
+    #[serde(deserialize_with = "at_least_one")]
+    pub width: u32,
+    #[serde(deserialize_with = "at_least_one")]
+    pub height: u32,
+    pub recursion_depth: usize,
+    #[serde(deserialize_with = "at_least_one")]
+    pub samples: u32, // per side of the pixel, so 4 means 16 rays
+    pub pattern: Pattern,
+    pub filter: Filter,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self { width: 600, height: 600, recursion_depth: 3, samples: 1, pattern: Pattern::Grid, filter: Filter::Box }
+    }
+}
+
+impl RenderSettings {
+    pub fn quality(&self) -> Quality {
+        Quality {
+            recursion_depth: self.recursion_depth,
+            sampling: Sampling { samples: self.samples, pattern: self.pattern, filter: self.filter },
+        }
+    }
+}
+
+pub struct SceneDescription {
+    pub scene: Scene,
+    pub render: RenderSettings,
+    pub files: Vec<PathBuf>, // everything read besides the scene itself, i.e. meshes and their .mtl files
+}
+
+pub fn load(path: &Path) -> Result<SceneDescription, SceneError> {
+    let src = std::fs::read_to_string(path)
+        .map_err(|source| SceneError::Io { path: path.to_path_buf(), source })?;
+    parse(&src, path.parent().unwrap_or(Path::new("")))
+}
+
+// relative paths in the scene (mesh files) are looked up from base_dir
+pub fn parse(src: &str, base_dir: &Path) -> Result<SceneDescription, SceneError> {
+    let toml_error = |e: toml::de::Error| SceneError::at(src, e.span(), e.message());
+
+    // objects and lights are told apart by their `type` key. serde's tagged
+    // enums buffer each entry to find it and lose where the fields were, so
+    // those two arrays come out here and get read entry by entry instead
+    let mut root = DeTable::parse(src).map_err(toml_error)?;
+    let objects = root.get_mut().remove("objects");
+    let lights = root.get_mut().remove("lights");
+
+    let file = SceneFile::deserialize(toml::de::Deserializer::from(root)).map_err(toml_error)?;
+    let objects: Vec<Spanned<ObjectDesc>> = tagged_entries(src, objects)?;
+    let lights: Vec<Spanned<LightDesc>> = tagged_entries(src, lights)?;
+
+    let mut scene = Scene::new();
+    let mut files = Vec::new();
+    scene.background_color = file.background_color.0;
+    if let Some(camera) = file.camera {
+        let span = camera.span();
+        scene.camera = camera.into_inner().build().map_err(|message| SceneError::at(src, Some(span), message))?;
+    }
+
+    for object in objects {
+        let span = object.span();
+        let objects = object.into_inner().build(base_dir, &mut files).map_err(|message| SceneError::at(src, Some(span), message))?;
+        for object in objects {
+            scene.add(object);
+        }
+    }
+
+    for light in lights {
+        let span = light.span();
+        let light = light.into_inner().build().map_err(|message| SceneError::at(src, Some(span), message))?;
+        scene.add_light(light);
+    }
+
+    scene.build_bvh();
+    Ok(SceneDescription { scene, render: file.render, files })
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneFile {
+    #[serde(default = "default_background")]
+    background_color: ColorValue,
+    camera: Option<Spanned<CameraDesc>>,
+    #[serde(default)]
+    render: RenderSettings,
+}
+
+// an [[objects]] or [[lights]] array. each entry's `type` becomes the key of
+// a one-entry table around the rest of it, which is how toml spells an
+// externally tagged enum, so everything keeps its place in the source
+fn tagged_entries<T: DeserializeOwned>(src: &str, array: Option<Spanned<DeValue>>) -> Result<Vec<Spanned<T>>, SceneError> {
+    let Some(array) = array else {
+        return Ok(Vec::new());
+    };
+    let array_span = array.span();
+    let DeValue::Array(entries) = array.into_inner() else {
+        return Err(SceneError::at(src, Some(array_span), "expected an array of tables"));
+    };
+
+    entries.into_iter().map(|entry| {
+        let span = entry.span();
+        let DeValue::Table(mut table) = entry.into_inner() else {
+            return Err(SceneError::at(src, Some(span), "expected a table"));
+        };
+        let Some(kind) = table.remove("type") else {
+            return Err(SceneError::at(src, Some(span), "missing `type`"));
+        };
+        let kind_span = kind.span();
+        let DeValue::String(kind) = kind.into_inner() else {
+            return Err(SceneError::at(src, Some(kind_span), "`type` should be a string"));
+        };
+
+        let mut tagged = DeTable::new();
+        tagged.insert(Spanned::new(kind_span, kind), Spanned::new(span.clone(), DeValue::Table(table)));
+        let value = T::deserialize(ValueDeserializer::from(Spanned::new(span.clone(), DeValue::Table(tagged))))
+            .map_err(|e| SceneError::at(src, e.span(), e.message()))?;
+        Ok(Spanned::new(span, value))
+    }).collect()
+}
+
+// so a zero points at the value itself
+fn at_least_one<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    match u32::deserialize(deserializer)? {
+        0 => Err(de::Error::custom("must be at least 1")),
+        n => Ok(n),
+    }
+}
+
+fn default_background() -> ColorValue {
+    ColorValue(Color::from_u32(0x101010))
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CameraDesc {
+    position: Vec3,
+    look_at: Option<Vec3>, // a point to face,
+    rotation: Option<Vec3>, // or degrees around x, then y, then z
+    up: Vec3,
+    fov: f64, // vertical, degrees
+}
+
+impl Default for CameraDesc {
+    fn default() -> Self {
+        Self {
+            position: Vec3([0.0; 3]),
+            look_at: None,
+            rotation: None,
+            up: Vec3([0.0, 1.0, 0.0]),
+            fov: Camera::default().fov.to_degrees(),
+        }
+    }
+}
+
+impl CameraDesc {
+    fn build(self) -> Result<Camera, String> {
+        if !(self.fov > 0.0 && self.fov < 180.0) {
+            return Err(format!("camera fov must be between 0 and 180 degrees, got {}", self.fov));
+        }
+        let mut camera = Camera::new(self.position.into(), self.fov.to_radians());
+
+        match (self.look_at, self.rotation) {
+            (Some(_), Some(_)) => return Err("camera takes look_at or rotation, not both".to_string()),
+            (Some(target), None) => {
+                let target = Point3D::from(target);
+                let up = check_direction("camera up", self.up)?;
+                let forward = target - camera.position;
+                if forward.len() == 0.0 {
+                    return Err("camera can't look at its own position".to_string());
+                }
+                if forward.cross(up).len() < 1e-9 * forward.len() * up.len() {
+                    return Err("camera up can't point the same way it's looking".to_string());
+                }
+                camera.point_at(target, up);
+            },
+            (None, Some(rotation)) => {
+                let [rx, ry, rz] = rotation.0.map(f64::to_radians);
+                camera.orientation = Matrix4::rotation_z(rz) * Matrix4::rotation_y(ry) * Matrix4::rotation_x(rx);
+            },
+            (None, None) => {},
+        }
+
+        Ok(camera)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
+enum ObjectDesc {
+    Sphere {
+        center: Vec3,
+        radius: f64,
+        color: ColorValue,
+        #[serde(default)]
+        specular: u32,
+        #[serde(default)]
+        reflective: f64,
+    },
+    Plane {
+        point: Vec3,
+        normal: Vec3,
+        color: ColorValue,
+        #[serde(default)]
+        specular: u32,
+        #[serde(default)]
+        reflective: f64,
+    },
+    Box {
+        min: Vec3, // opposite corners
+        max: Vec3,
+        color: ColorValue,
+        #[serde(default)]
+        specular: u32,
+        #[serde(default)]
+        reflective: f64,
+    },
+    Disk {
+        center: Vec3,
+        normal: Vec3,
+        radius: f64,
+        color: ColorValue,
+        #[serde(default)]
+        specular: u32,
+        #[serde(default)]
+        reflective: f64,
+    },
+    Cylinder {
+        base: Vec3, // center of the bottom cap
+        axis: Vec3,
+        radius: f64,
+        height: f64,
+        color: ColorValue,
+        #[serde(default)]
+        specular: u32,
+        #[serde(default)]
+        reflective: f64,
+    },
+    Cone {
+        base: Vec3, // center of the base, the tip is height along axis
+        axis: Vec3,
+        radius: f64,
+        height: f64,
+        color: ColorValue,
+        #[serde(default)]
+        specular: u32,
+        #[serde(default)]
+        reflective: f64,
+    },
+    Torus {
+        center: Vec3,
+        axis: Vec3,
+        major_radius: f64,
+        minor_radius: f64,
+        color: ColorValue,
+        #[serde(default)]
+        specular: u32,
+        #[serde(default)]
+        reflective: f64,
+    },
+    Triangle {
+        vertices: [Vec3; 3], // clockwise seen from the front
+        normals: Option<[Vec3; 3]>, // per vertex, for smooth shading
+        color: ColorValue,
+        #[serde(default)]
+        specular: u32,
+        #[serde(default)]
+        reflective: f64,
+    },
+    Mesh {
+        file: PathBuf, // a Wavefront .obj, relative to the scene file
+        #[serde(default = "zero")]
+        position: Vec3,
+        #[serde(default = "zero")]
+        rotation: Vec3, // degrees around x, then y, then z
+        #[serde(default = "one")]
+        scale: f64,
+        color: Option<ColorValue>, // defaults to the .mtl colors
+        specular: Option<u32>,
+        #[serde(default)]
+        reflective: f64,
+    },
+}
+
+fn zero() -> Vec3 {
+    Vec3([0.0; 3])
+}
+
+fn one() -> f64 {
+    1.0
+}
+
+impl ObjectDesc {
+    // any files it reads get added to `files`
+    fn build(self, base_dir: &Path, files: &mut Vec<PathBuf>) -> Result<Vec<Box<dyn Primitive>>, String> {
+        match self {
+            ObjectDesc::Sphere { center, radius, color, specular, reflective } => {
+                check_positive("sphere radius", radius)?;
+                check_reflective(reflective)?;
+                Ok(vec![Box::new(Sphere::new(center.into(), radius, color.0, specular, reflective))])
+            },
+            ObjectDesc::Plane { point, normal, color, specular, reflective } => {
+                check_reflective(reflective)?;
+                let normal = check_direction("plane normal", normal)?;
+                Ok(vec![Box::new(Plane::new(point.into(), normal, color.0, specular, reflective))])
+            },
+            ObjectDesc::Box { min, max, color, specular, reflective } => {
+                check_reflective(reflective)?;
+                let (min, max) = (Point3D::from(min), Point3D::from(max));
+                if min.x == max.x || min.y == max.y || min.z == max.z {
+                    return Err("box has no volume".to_string());
+                }
+                Ok(vec![Box::new(AxisAlignedBox::new(min, max, color.0, specular, reflective))])
+            },
+            ObjectDesc::Disk { center, normal, radius, color, specular, reflective } => {
+                check_reflective(reflective)?;
+                let normal = check_direction("disk normal", normal)?;
+                check_positive("disk radius", radius)?;
+                Ok(vec![Box::new(Disk::new(center.into(), normal, radius, color.0, specular, reflective))])
+            },
+            ObjectDesc::Cylinder { base, axis, radius, height, color, specular, reflective } => {
+                check_reflective(reflective)?;
+                let axis = check_direction("cylinder axis", axis)?;
+                check_positive("cylinder radius", radius)?;
+                check_positive("cylinder height", height)?;
+                Ok(vec![Box::new(Cylinder::new(base.into(), axis, radius, height, color.0, specular, reflective))])
+            },
+            ObjectDesc::Cone { base, axis, radius, height, color, specular, reflective } => {
+                check_reflective(reflective)?;
+                let axis = check_direction("cone axis", axis)?;
+                check_positive("cone radius", radius)?;
+                check_positive("cone height", height)?;
+                Ok(vec![Box::new(Cone::new(base.into(), axis, radius, height, color.0, specular, reflective))])
+            },
+            ObjectDesc::Torus { center, axis, major_radius, minor_radius, color, specular, reflective } => {
+                check_reflective(reflective)?;
+                let axis = check_direction("torus axis", axis)?;
+                check_positive("torus major_radius", major_radius)?;
+                check_positive("torus minor_radius", minor_radius)?;
+                Ok(vec![Box::new(Torus::new(center.into(), axis, major_radius, minor_radius, color.0, specular, reflective))])
+            },
+            ObjectDesc::Triangle { vertices, normals, color, specular, reflective } => {
+                check_reflective(reflective)?;
+                let vertices = vertices.map(Point3D::from);
+                let triangle = match normals {
+                    None => Triangle::new(vertices, color.0, specular, reflective),
+                    Some(normals) => Triangle::with_normals(vertices, normals.map(Vector3::from), color.0, specular, reflective),
+                };
+                if !triangle.face_normal().len().is_finite() {
+                    return Err("triangle is degenerate (its vertices are in a line)".to_string());
+                }
+                Ok(vec![Box::new(triangle)])
+            },
+            ObjectDesc::Mesh { file, position, rotation, scale, color, specular, reflective } => {
+                check_reflective(reflective)?;
+                check_positive("mesh scale", scale)?;
+                let path = base_dir.join(file);
+                let mesh = Mesh::load_obj(&path).map_err(|e| e.to_string())?;
+                let dir = path.parent().unwrap_or(Path::new(""));
+                files.extend(mesh.material_libs.iter().map(|lib| dir.join(lib)));
+                files.push(path);
+
+                let [rx, ry, rz] = rotation.0.map(f64::to_radians);
+                let transform = Matrix4::translation(position.into())
+                    * Matrix4::rotation_z(rz)
+                    * Matrix4::rotation_y(ry)
+                    * Matrix4::rotation_x(rx)
+                    * Matrix4::uniform_scale(scale);
+
+                Ok(build_mesh(&mesh, transform, color.map(|c| c.0), specular, reflective))
+            },
+        }
+    }
+}
+
+// one TriangleMesh per material, since a primitive only has the one color.
+// a color from the scene file overrides the lot
+fn build_mesh(mesh: &Mesh, transform: Matrix4, color: Option<Color>, specular: Option<u32>, reflective: f64) -> Vec<Box<dyn Primitive>> {
+    let mut groups: Vec<(Option<usize>, Vec<Triangle>)> = Vec::new();
+    let default_material = Material::named("");
+
+    for face in &mesh.faces {
+        let key = if color.is_some() { None } else { face.material };
+        let material = key.map(|i| &mesh.materials[i]).unwrap_or(&default_material);
+        let color = color.unwrap_or_else(|| Color::from(material.diffuse));
+        let specular = specular.unwrap_or_else(|| material.specular_exponent());
+
+        let vertices = face.vertices.map(|v| transform.transform_point(mesh.positions[v.position]));
+        let normals = face.vertices.map(|v| v.normal.map(|n| transform.transform_vector(mesh.normals[n])));
+        let triangle = match normals {
+            [Some(a), Some(b), Some(c)] => Triangle::with_normals(vertices, [a, b, c], color, specular, reflective),
+            _ => Triangle::new(vertices, color, specular, reflective),
+        };
+        if !triangle.face_normal().len().is_finite() {
+            continue; // exporters leave the odd sliver behind, nothing to hit anyway
+        }
+
+        match groups.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, triangles)) => triangles.push(triangle),
+            None => groups.push((key, vec![triangle])),
+        }
+    }
+
+    groups.into_iter()
+        .map(|(key, triangles)| {
+            let material = key.map(|i| &mesh.materials[i]).unwrap_or(&default_material);
+            let mesh = TriangleMesh::new(
+                triangles,
+                color.unwrap_or_else(|| Color::from(material.diffuse)),
+                specular.unwrap_or_else(|| material.specular_exponent()),
+                reflective,
+            );
+            Box::new(mesh) as Box<dyn Primitive>
+        })
+        .collect()
+}
+
+fn check_positive(name: &str, value: f64) -> Result<(), String> {
+    if value > 0.0 {
+        Ok(())
+    } else {
+        Err(format!("{name} must be positive, got {value}"))
+    }
+}
+
+fn check_direction(name: &str, v: Vec3) -> Result<Vector3, String> {
+    let v = Vector3::from(v);
+    if v.len() > 0.0 {
+        Ok(v)
+    } else {
+        Err(format!("{name} can't be zero"))
+    }
+}
+
+fn check_reflective(reflective: f64) -> Result<(), String> {
+    if (0.0..=1.0).contains(&reflective) {
+        Ok(())
+    } else {
+        Err(format!("reflective must be between 0 and 1, got {reflective}"))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
+enum LightDesc {
+    Ambient { intensity: f64 },
+    Point { intensity: f64, position: Vec3 },
+    Directional { intensity: f64, direction: Vec3 },
+}
+
+impl LightDesc {
+    fn build(self) -> Result<Light, String> {
+        let light = match self {
+            LightDesc::Ambient { intensity } => Light::new_ambient(intensity),
+            LightDesc::Point { intensity, position } => Light::new_point(intensity, position.into()),
+            LightDesc::Directional { intensity, direction } => Light::new_directional(intensity, direction.into()),
+        };
+
+        if light.intensity() < 0.0 {
+            return Err(format!("light intensity can't be negative, got {}", light.intensity()));
+        }
+        Ok(light)
+    }
+}
+
+#[derive(Deserialize)]
+struct Vec3([f64; 3]);
+
+impl From<Vec3> for Point3D {
+    fn from(v: Vec3) -> Self {
+        Point3D::new(v.0[0], v.0[1], v.0[2])
+    }
+}
+
+impl From<Vec3> for Vector3 {
+    fn from(v: Vec3) -> Self {
+        Vector3 { x: v.0[0], y: v.0[1], z: v.0[2] }
+    }
+}
+
+// "#RRGGBB" or a plain 0xRRGGBB integer
+struct ColorValue(Color);
+
+impl<'de> Deserialize<'de> for ColorValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ColorVisitor;
+
+        impl Visitor<'_> for ColorVisitor {
+            type Value = ColorValue;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a color like \"#FF8800\" or 0xFF8800")
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<ColorValue, E> {
+                if (0..=0xFFFFFF).contains(&v) {
+                    Ok(ColorValue(Color::from_u32(v as u32)))
+                } else {
+                    Err(E::custom(format!("color {v:#x} is out of range")))
+                }
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<ColorValue, E> {
+                let hex = v.strip_prefix('#')
+                    .filter(|h| h.len() == 6 && h.chars().all(|c| c.is_ascii_hexdigit()));
+                match hex.and_then(|h| u32::from_str_radix(h, 16).ok()) {
+                    Some(c) => Ok(ColorValue(Color::from_u32(c))),
+                    None => Err(E::invalid_value(de::Unexpected::Str(v), &self)),
+                }
+            }
+        }
+
+        deserializer.deserialize_any(ColorVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn demo_scene_loads() {
+        let description = parse(DEMO_SCENE, Path::new("")).unwrap();
+
+        assert_eq!(description.scene.objects.len(), 4);
+        assert_eq!(description.scene.lights.len(), 3);
+        assert_eq!(description.render.recursion_depth, 3);
+        assert!(description.files.is_empty());
+    }
+
+    #[test]
+    fn remembers_the_files_meshes_read() {
+        let description = load(Path::new("scenes/mesh.toml")).unwrap();
+
+        let names: Vec<_> = description.files.iter().map(|path| path.file_name().unwrap()).collect();
+        assert_eq!(names, ["prism.mtl", "prism.obj"]);
+        assert!(description.files.iter().all(|path| path.exists()));
+    }
+
+    fn error_at(src: &str) -> (usize, usize) {
+        match parse(src, Path::new("")) {
+            Err(SceneError::Parse { line, column, .. }) => (line, column),
+            Err(e) => panic!("expected a parse error, got {e}"),
+            Ok(_) => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        let src = "[render]\nwidth = 10\n\n[[objects]]\ntype = \"sphere\"\ncenter = [0, 0, 1]\nradius = 1\ncolor = \"red\"\n";
+        assert_eq!(error_at(src), (8, 9));
+
+        // a bad field type, a field that doesn't belong and an unknown type
+        assert_eq!(error_at("[[lights]]\ntype = \"point\"\nintensity = \"lots\"\nposition = [0, 0, 0]\n"), (3, 13));
+        assert_eq!(error_at("[[lights]]\ntype = \"ambient\"\nintensity = 0.2\nposition = [0, 0, 0]\n"), (4, 1));
+        assert_eq!(error_at("[[objects]]\n\ntype = \"blob\"\n"), (3, 8));
+    }
+
+    #[test]
+    fn render_errors_point_at_the_value() {
+        assert_eq!(error_at("background_color = 0\n\n[render]\nwidth = 10\nheight = 0\n"), (5, 10));
+        assert_eq!(error_at("[render]\nsamples = 0\n"), (2, 11));
+        assert_eq!(error_at("[render]\nwidth = -3\n"), (2, 9));
+    }
+
+    #[test]
+    fn checks_past_parsing_point_at_the_entry() {
+        let src = "[[objects]]\ntype = \"sphere\"\ncenter = [0, 0, 1]\nradius = 1\ncolor = 0xFF0000\n\n[[objects]]\ntype = \"sphere\"\ncenter = [0, 0, 1]\nradius = -1\ncolor = 0xFF0000\n";
+        assert_eq!(error_at(src).0, 7);
+    }
+}
@@ -0,0 +1,119 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+// polls files' modification times. no inotify and friends, just a stat
+// every `interval`, which is plenty for a human hitting save
+pub struct FileWatcher {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    next_check: Instant,
+    interval: Duration,
+}
+
+impl FileWatcher {
+    pub fn new(paths: &[PathBuf], interval: Duration) -> Self {
+        Self {
+            files: paths.iter().map(|path| (path.clone(), modified_time(path))).collect(),
+            next_check: Instant::now() + interval,
+            interval,
+        }
+    }
+
+    pub fn next_check(&self) -> Instant {
+        self.next_check
+    }
+
+    // true once per change, however many of the files changed. a file that
+    // vanishes for a moment (editors that save via rename) is not a change
+    pub fn poll(&mut self) -> bool {
+        let now = Instant::now();
+        if now < self.next_check {
+            return false;
+        }
+        self.next_check = now + self.interval;
+
+        let mut changed = false;
+        for (path, modified) in &mut self.files {
+            match modified_time(path) {
+                Some(time) if Some(time) != *modified => {
+                    *modified = Some(time);
+                    changed = true;
+                },
+                _ => (),
+            }
+        }
+        changed
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, File};
+
+    // its own file per test, since they run in parallel
+    fn temp_file(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("raytracer-watch-{}-{name}", std::process::id()));
+        fs::write(&path, "").unwrap();
+        path
+    }
+
+    // writes can land in the same tick of a coarse clock, so set the time outright
+    fn touch(path: &Path, seconds: u64) {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000 + seconds);
+        File::options().write(true).open(path).unwrap().set_modified(time).unwrap();
+    }
+
+    #[test]
+    fn reports_each_change_once() {
+        let (a, b) = (temp_file("once-a"), temp_file("once-b"));
+        let mut watcher = FileWatcher::new(&[a.clone(), b.clone()], Duration::ZERO);
+        assert!(!watcher.poll());
+
+        touch(&a, 1);
+        assert!(watcher.poll());
+        assert!(!watcher.poll());
+
+        // either file will do
+        touch(&b, 2);
+        assert!(watcher.poll());
+        assert!(!watcher.poll());
+
+        fs::remove_file(a).unwrap();
+        fs::remove_file(b).unwrap();
+    }
+
+    #[test]
+    fn waits_for_the_interval() {
+        let path = temp_file("interval");
+        let mut watcher = FileWatcher::new(std::slice::from_ref(&path), Duration::from_secs(3600));
+        assert!(watcher.next_check() > Instant::now());
+
+        touch(&path, 1);
+        assert!(!watcher.poll());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn vanishing_is_not_a_change() {
+        let path = temp_file("rename");
+        touch(&path, 1);
+        let mut watcher = FileWatcher::new(std::slice::from_ref(&path), Duration::ZERO);
+
+        // what an editor saving via rename looks like in between
+        fs::remove_file(&path).unwrap();
+        assert!(!watcher.poll());
+
+        // and the new file showing up is
+        fs::write(&path, "saved").unwrap();
+        touch(&path, 2);
+        assert!(watcher.poll());
+        assert!(!watcher.poll());
+
+        fs::remove_file(path).unwrap();
+    }
+}